@@ -0,0 +1,214 @@
+use std::{
+    env::consts::{ARCH, OS},
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+use semver_eq::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::{cache_ttl, is_offline, release_source};
+use crate::runtime::Runtime;
+use crate::utils::{fetch_releases, wasmenv_cache_dir, FetchedReleases, Release, ReleaseAsset};
+
+/// Releases of a runtime as last fetched from GitHub
+#[derive(Debug, Deserialize, Serialize)]
+struct ReleaseIndex {
+    /// Seconds since the unix epoch at which the index was fetched or last revalidated
+    fetched_at: u64,
+    etag: Option<String>,
+    releases: Vec<Release>,
+}
+
+impl ReleaseIndex {
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        now().saturating_sub(self.fetched_at) < ttl.as_secs()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Name of the cached release index of a runtime fetched from `source`. Every source gets
+/// its own index, as their releases and `ETag`s can't be mixed.
+fn release_index_name(runtime: &dyn Runtime, source: &str) -> String {
+    let source_digest = format!("{:x}", Sha256::digest(source.as_bytes()));
+    format!("{}-{}.json", runtime.name(), &source_digest[..16])
+}
+
+fn release_index_path(runtime: &dyn Runtime) -> anyhow::Result<PathBuf> {
+    Ok(wasmenv_cache_dir()?
+        .join("releases")
+        .join(release_index_name(runtime, &release_source()?)))
+}
+
+/// Reads the cached release index. A missing or unreadable index is treated as not cached.
+fn read_release_index(runtime: &dyn Runtime) -> Option<ReleaseIndex> {
+    let contents = fs::read(release_index_path(runtime).ok()?).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+fn write_release_index(runtime: &dyn Runtime, index: &ReleaseIndex) -> anyhow::Result<()> {
+    let path = release_index_path(runtime)?;
+    fs::create_dir_all(path.parent().unwrap())?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_vec(index)?)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+/// Returns the releases of a runtime, newest first, from the on-disk cache when it is fresh.
+/// A stale index is revalidated with its `ETag`, and still used if GitHub can't be reached.
+///
+/// When offline, only the cached index and the releases found in the download cache are used.
+pub fn cached_releases(runtime: &dyn Runtime) -> anyhow::Result<Vec<Release>> {
+    let index = read_release_index(runtime);
+    if is_offline()? {
+        return offline_releases(runtime, index);
+    }
+    let ttl = cache_ttl()?;
+    if let Some(index) = index.as_ref().filter(|index| index.is_fresh(ttl)) {
+        return Ok(index.releases.clone());
+    }
+
+    let etag = index.as_ref().and_then(|index| index.etag.as_deref());
+    let fetched = match fetch_releases(runtime, etag) {
+        Ok(fetched) => fetched,
+        Err(err) => match index {
+            Some(index) => {
+                eprintln!(
+                    "Could not fetch {} releases ({}), using the cached list instead.",
+                    runtime.name(),
+                    err
+                );
+                return Ok(index.releases);
+            }
+            None => return Err(err),
+        },
+    };
+    let index = match (fetched, index) {
+        (Some(FetchedReleases { releases, etag }), _) => ReleaseIndex {
+            fetched_at: now(),
+            etag,
+            releases,
+        },
+        (None, Some(index)) => ReleaseIndex {
+            fetched_at: now(),
+            ..index
+        },
+        (None, None) => {
+            return Err(anyhow::anyhow!(
+                "The release source said the {} releases were not modified, but none are cached",
+                runtime.name()
+            ))
+        }
+    };
+    write_release_index(runtime, &index)?;
+    Ok(index.releases)
+}
+
+fn offline_releases(
+    runtime: &dyn Runtime,
+    index: Option<ReleaseIndex>,
+) -> anyhow::Result<Vec<Release>> {
+    let mut releases = index.map(|index| index.releases).unwrap_or_default();
+    for release in downloaded_releases(runtime)? {
+        if !releases
+            .iter()
+            .any(|rel| rel.version() == release.version())
+        {
+            releases.push(release);
+        }
+    }
+    if releases.is_empty() {
+        return Err(anyhow::anyhow!(
+            "wasmenv is offline and no {} releases have been cached yet",
+            runtime.name()
+        ));
+    }
+    releases.sort_by_key(|rel| std::cmp::Reverse(rel.version()));
+    Ok(releases)
+}
+
+/// Builds releases from the archives in the download cache, which are named
+/// `<version>-<asset name>`
+fn downloaded_releases(runtime: &dyn Runtime) -> anyhow::Result<Vec<Release>> {
+    let cache_dir = wasmenv_cache_dir()?;
+    let Ok(entries) = fs::read_dir(&cache_dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut releases = Vec::new();
+    for entry in entries.flatten() {
+        let filename = entry.file_name().to_string_lossy().to_string();
+        let Some((version, asset_name)) = split_download_filename(runtime, &filename) else {
+            continue;
+        };
+        let published_at: DateTime<Utc> = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(UNIX_EPOCH)
+            .into();
+        releases.push(Release {
+            tag_name: format!("v{version}"),
+            prerelease: !version.pre.is_empty(),
+            published_at: published_at.to_rfc3339(),
+            assets: vec![ReleaseAsset {
+                name: asset_name.to_string(),
+                browser_download_url: entry.path().to_string_lossy().to_string(),
+                digest: None,
+            }],
+        });
+    }
+    Ok(releases)
+}
+
+fn split_download_filename<'a>(
+    runtime: &dyn Runtime,
+    filename: &'a str,
+) -> Option<(Version, &'a str)> {
+    filename
+        .match_indices('-')
+        .map(|(index, _)| (&filename[..index], &filename[index + 1..]))
+        .find_map(|(version, asset_name)| {
+            let version = Version::parse(version).ok()?;
+            (runtime.asset_name(OS, ARCH, &version)? == asset_name).then_some((version, asset_name))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{Wasmer, Wasmtime};
+
+    #[test]
+    fn test_release_index_name() {
+        let github = release_index_name(&Wasmer, "https://api.github.com");
+        let mirror = release_index_name(&Wasmer, "https://mirror.example.com/github-api");
+        assert!(github.starts_with("wasmer-") && github.ends_with(".json"));
+        assert_ne!(github, mirror);
+        assert_ne!(
+            github,
+            release_index_name(&Wasmtime, "https://api.github.com")
+        );
+    }
+
+    #[test]
+    fn test_split_download_filename() {
+        let asset_name = Wasmer.asset_name(OS, ARCH, &Version::new(4, 2, 0)).unwrap();
+        let filename = format!("4.2.0-beta.1-{asset_name}");
+        let (version, name) = split_download_filename(&Wasmer, &filename).unwrap();
+        assert_eq!(version.to_string(), "4.2.0-beta.1");
+        assert_eq!(name, asset_name);
+
+        assert!(split_download_filename(&Wasmtime, &filename).is_none());
+        assert!(split_download_filename(&Wasmer, "releases").is_none());
+    }
+}
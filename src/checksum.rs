@@ -0,0 +1,224 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::copy,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+
+use crate::config::asset_url;
+use crate::runtime::Runtime;
+use crate::utils::{http_client, with_retries, Release};
+
+/// Computes the hex encoded SHA-256 digest of a file
+pub fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut file = File::open(path).context(format!("Open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// File next to a cached download recording its verified digest
+fn digest_path(path: &Path) -> PathBuf {
+    let mut filename = path.file_name().unwrap_or_default().to_os_string();
+    filename.push(".sha256");
+    path.with_file_name(filename)
+}
+
+fn recorded_digest(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(digest_path(path)).ok()?;
+    let filename = path.file_name()?.to_str()?;
+    parse_checksums(&contents, filename)
+}
+
+/// Records the verified digest of a cached download next to it
+pub fn record_digest(path: &Path, digest: &str) -> anyhow::Result<()> {
+    let filename = path.file_name().unwrap_or_default().to_string_lossy();
+    fs::write(digest_path(path), format!("{digest}  {filename}\n"))?;
+    Ok(())
+}
+
+/// Removes a cached download along with its recorded digest
+pub fn remove_download(path: &Path) -> anyhow::Result<()> {
+    fs::remove_file(path)?;
+    let digest_path = digest_path(path);
+    if digest_path.exists() {
+        fs::remove_file(digest_path)?;
+    }
+    Ok(())
+}
+
+/// Finds the digest of `filename` in the contents of a checksum file, either in the
+/// `sha256sum` format or a lone digest
+fn parse_checksums(contents: &str, filename: &str) -> Option<String> {
+    let digests: Vec<(&str, Option<&str>)> = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            let digest = parts.next().unwrap_or_default();
+            (
+                digest,
+                parts.next().map(|name| name.trim_start_matches('*')),
+            )
+        })
+        .collect();
+    let digest = match digests.as_slice() {
+        [(digest, None)] => digest,
+        _ => digests
+            .iter()
+            .find(|(_, name)| *name == Some(filename))
+            .map(|(digest, _)| digest)?,
+    };
+    let digest = digest.to_lowercase();
+    let is_sha256 = digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit());
+    is_sha256.then_some(digest)
+}
+
+/// Looks up the published SHA-256 digest of the runtime's asset in a release, either from
+/// the digest GitHub reports for the asset or from a checksum file attached to the release
+fn published_digest(runtime: &dyn Runtime, release: &Release) -> anyhow::Result<Option<String>> {
+    let Some(asset) = release.asset(runtime) else {
+        return Ok(None);
+    };
+    if let Some(digest) = asset
+        .digest
+        .as_deref()
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+    {
+        return Ok(Some(digest.to_lowercase()));
+    }
+
+    let checksum_files = [
+        format!("{}.sha256", asset.name),
+        format!("{}.sha256sum", asset.name),
+        String::from("checksums.txt"),
+        String::from("sha256sums.txt"),
+        String::from("SHA256SUMS"),
+    ];
+    let Some(checksum_asset) = checksum_files
+        .iter()
+        .find_map(|name| release.assets.iter().find(|asset| &asset.name == name))
+    else {
+        return Ok(None);
+    };
+    let url = asset_url(&checksum_asset.browser_download_url)?;
+    let contents = with_retries("Fetching checksums", || {
+        Ok(http_client()?
+            .get(&url)
+            .send()?
+            .error_for_status()?
+            .text()?)
+    })?;
+    Ok(parse_checksums(&contents, &asset.name))
+}
+
+/// A file whose digest differs from the expected one, as opposed to failing to find out
+/// which digest to expect
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    path: PathBuf,
+    expected: String,
+    actual: String,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Checksum mismatch for {:?}: expected {}, got {}",
+            self.path, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+fn check_digest(path: &Path, expected: &str, actual: &str) -> anyhow::Result<()> {
+    if expected != actual {
+        return Err(ChecksumMismatch {
+            path: path.to_path_buf(),
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Verifies a freshly downloaded release against its published checksum, returning its
+/// digest. Releases without a published checksum still get their digest returned, so that
+/// later corruption of the cached file can be noticed once it is recorded.
+pub fn verify_download(
+    runtime: &dyn Runtime,
+    release: &Release,
+    path: &Path,
+) -> anyhow::Result<String> {
+    let actual = sha256_file(path)?;
+    if let Some(expected) = published_digest(runtime, release)? {
+        check_digest(path, &expected, &actual)?;
+    }
+    Ok(actual)
+}
+
+/// Verifies a cached download against the digest recorded when it was downloaded, and the
+/// digest GitHub reports for it if the release list has one
+pub fn verify_cached(runtime: &dyn Runtime, release: &Release, path: &Path) -> anyhow::Result<()> {
+    let actual = sha256_file(path)?;
+    let reported = release
+        .asset(runtime)
+        .and_then(|asset| asset.digest.as_deref())
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+        .map(str::to_lowercase);
+    let recorded = recorded_digest(path);
+    for expected in [&reported, &recorded].into_iter().flatten() {
+        check_digest(path, expected, &actual)?;
+    }
+    if recorded.is_none() {
+        record_digest(path, &actual)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn test_parse_checksums() {
+        assert_eq!(
+            parse_checksums(&format!("{DIGEST}\n"), "wasmer.tar.gz").as_deref(),
+            Some(DIGEST)
+        );
+        let sums = format!(
+            "{}  wasmer-darwin-amd64.tar.gz\n{} *wasmer-linux-amd64.tar.gz\n",
+            "0".repeat(64),
+            DIGEST.to_uppercase()
+        );
+        assert_eq!(
+            parse_checksums(&sums, "wasmer-linux-amd64.tar.gz").as_deref(),
+            Some(DIGEST)
+        );
+        assert_eq!(parse_checksums(&sums, "wasmer-windows.exe"), None);
+        assert_eq!(parse_checksums("not a digest", "wasmer.tar.gz"), None);
+    }
+
+    #[test]
+    fn test_check_digest() {
+        assert!(check_digest(Path::new("wasmer.tar.gz"), DIGEST, DIGEST).is_ok());
+        let err = check_digest(Path::new("wasmer.tar.gz"), DIGEST, &"0".repeat(64)).unwrap_err();
+        assert!(err.is::<ChecksumMismatch>());
+    }
+
+    #[test]
+    fn test_sha256_file() -> anyhow::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        fs::write(file.path(), "test")?;
+        assert_eq!(sha256_file(file.path())?, DIGEST);
+        Ok(())
+    }
+}
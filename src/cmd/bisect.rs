@@ -0,0 +1,215 @@
+use semver_eq::Version;
+
+use crate::cmd::exec::{exec_target_command, exit_code, ExecCommand, ExecTarget};
+use crate::cmd::install::ensure_release_installed;
+use crate::runtime::Runtime;
+use crate::utils::{releases_to_install, verify_wasmenv_is_in_path, Release};
+
+/// Exit code of a test command which couldn't tell whether a version is good, as in
+/// `git bisect run`
+const SKIP_EXIT_CODE: i32 = 125;
+
+/// What the test command said about a version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    Good,
+    Bad,
+    Skip,
+}
+
+impl Verdict {
+    /// Reads the exit code of the test command the way `git bisect run` does. Exit codes
+    /// above 127 abort the bisection, as they mostly mean the command got killed.
+    fn from_exit_code(code: i32) -> anyhow::Result<Verdict> {
+        match code {
+            0 => Ok(Verdict::Good),
+            SKIP_EXIT_CODE => Ok(Verdict::Skip),
+            1..=127 => Ok(Verdict::Bad),
+            _ => Err(anyhow::anyhow!(
+                "The test command exited with {}, stopping the bisection.",
+                code
+            )),
+        }
+    }
+}
+
+/// Where the bisection ended up, as indices into the candidates
+#[derive(Debug, PartialEq, Eq)]
+struct Bisection {
+    /// The first candidate known to be bad
+    first_bad: usize,
+    /// Skipped candidates right before `first_bad`, any of which may be the first bad one
+    skipped: Vec<usize>,
+}
+
+/// Binary searches `count` candidates, ordered oldest first, for the first bad one. The
+/// version before the first candidate is known to be good and the last candidate is known
+/// to be bad. `test` gets the candidate to test and roughly how many tests are left.
+fn bisect(
+    count: usize,
+    mut test: impl FnMut(usize, u32) -> anyhow::Result<Verdict>,
+) -> anyhow::Result<Bisection> {
+    let mut good = 0;
+    let mut bad = count - 1;
+    let mut skipped = Vec::new();
+    loop {
+        let untested: Vec<usize> = (good..bad).filter(|i| !skipped.contains(i)).collect();
+        if untested.is_empty() {
+            break;
+        }
+        let candidate = untested[untested.len() / 2];
+        let steps_left = usize::BITS - untested.len().leading_zeros();
+        match test(candidate, steps_left)? {
+            Verdict::Good => good = candidate + 1,
+            Verdict::Bad => bad = candidate,
+            Verdict::Skip => skipped.push(candidate),
+        }
+    }
+    let mut skipped: Vec<usize> = skipped
+        .into_iter()
+        .filter(|i| (good..bad).contains(i))
+        .collect();
+    skipped.sort();
+    Ok(Bisection {
+        first_bad: bad,
+        skipped,
+    })
+}
+
+fn test_release(
+    runtime: &dyn Runtime,
+    release: &Release,
+    command: &ExecCommand,
+    steps_left: u32,
+) -> anyhow::Result<Verdict> {
+    ensure_release_installed(runtime, release)?;
+    println!(
+        "Testing {} {} (at most {} to go)",
+        runtime.name(),
+        release.version(),
+        steps_left
+    );
+    let target = ExecTarget::installed(runtime, &release.version())?;
+    let status = exec_target_command(runtime, &target, command)?.status()?;
+    let verdict = Verdict::from_exit_code(exit_code(status))?;
+    println!(
+        "{} {} is {}",
+        runtime.name(),
+        release.version(),
+        match verdict {
+            Verdict::Good => "good",
+            Verdict::Bad => "bad",
+            Verdict::Skip => "skipped",
+        }
+    );
+    Ok(verdict)
+}
+
+/// Finds the first release between a good and a bad version for which the test command fails
+pub fn bisect_releases(
+    runtime: &dyn Runtime,
+    good: Version,
+    bad: Version,
+    command: Vec<String>,
+    install_prerelease: bool,
+) -> anyhow::Result<()> {
+    verify_wasmenv_is_in_path()?;
+    if good >= bad {
+        return Err(anyhow::anyhow!(
+            "The good version ({}) has to be older than the bad version ({}).",
+            good,
+            bad
+        ));
+    }
+    let mut candidates: Vec<Release> = releases_to_install(runtime, &None, install_prerelease)?
+        .into_iter()
+        .filter(|release| release.version() > good && release.version() <= bad)
+        .filter(|release| release.asset(runtime).is_some())
+        .collect();
+    candidates.reverse();
+    if candidates.last().map(Release::version) != Some(bad.clone()) {
+        return Err(anyhow::anyhow!("{} {} was not found.", runtime.name(), bad));
+    }
+
+    let command = ExecCommand::Program(command);
+    let bisection = bisect(candidates.len(), |candidate, steps_left| {
+        test_release(runtime, &candidates[candidate], &command, steps_left)
+    })?;
+
+    let first_bad = &candidates[bisection.first_bad];
+    if !bisection.skipped.is_empty() {
+        let versions: Vec<String> = bisection
+            .skipped
+            .iter()
+            .chain([&bisection.first_bad])
+            .map(|i| candidates[*i].version().to_string())
+            .collect();
+        println!(
+            "Some releases were skipped, the first bad {} release could be any of: {}",
+            runtime.name(),
+            versions.join(", ")
+        );
+        return Ok(());
+    }
+    println!(
+        "The first bad {} release is {} (tag {}), published {}.",
+        runtime.name(),
+        first_bad.version(),
+        first_bad.tag_name,
+        first_bad.published_time()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bisect() -> anyhow::Result<()> {
+        let mut tested = Vec::new();
+        let bisection = bisect(10, |candidate, _| {
+            tested.push(candidate);
+            Ok(if candidate < 6 {
+                Verdict::Good
+            } else {
+                Verdict::Bad
+            })
+        })?;
+        assert_eq!(
+            bisection,
+            Bisection {
+                first_bad: 6,
+                skipped: vec![]
+            }
+        );
+        assert!(tested.len() <= 4);
+        assert!(!tested.contains(&9));
+
+        let bisection = bisect(10, |candidate, _| {
+            Ok(match candidate {
+                5 => Verdict::Skip,
+                0..=4 => Verdict::Good,
+                _ => Verdict::Bad,
+            })
+        })?;
+        assert_eq!(
+            bisection,
+            Bisection {
+                first_bad: 6,
+                skipped: vec![5]
+            }
+        );
+
+        assert_eq!(bisect(1, |_, _| unreachable!())?.first_bad, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verdict_from_exit_code() {
+        assert_eq!(Verdict::from_exit_code(0).unwrap(), Verdict::Good);
+        assert_eq!(Verdict::from_exit_code(125).unwrap(), Verdict::Skip);
+        assert_eq!(Verdict::from_exit_code(1).unwrap(), Verdict::Bad);
+        assert!(Verdict::from_exit_code(130).is_err());
+    }
+}
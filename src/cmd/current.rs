@@ -1,16 +1,80 @@
-use crate::utils::{find_current_wasmer, find_current_wasmer_dir, verify_wasmenv_is_in_path};
+use crate::project::{RequestedVersion, VERSION_PRECEDENCE};
+use crate::runtime::Runtime;
+use crate::utils::{
+    find_current_dir, find_current_version, find_installed_version, verify_wasmenv_is_in_path,
+};
 
-pub fn current(verbose: bool) -> anyhow::Result<()> {
+/// Shows the installed version matching the requested one, e.g. from a project's version file.
+/// Without shims or the shell hook, the runtime on `PATH` may still be another version, which
+/// is pointed out.
+fn current_requested(
+    runtime: &dyn Runtime,
+    requested: RequestedVersion,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let installed = find_installed_version(runtime, &requested.version, requested.prerelease)?;
+    let Some(installed) = installed else {
+        return Err(anyhow::anyhow!(
+            "{} `{}` requested by {} is not installed.\nRun `wasmenv use` to install it.",
+            runtime.name(),
+            requested.version,
+            requested.source
+        ));
+    };
+    println!("{} {}", runtime.name(), installed);
+    match find_current_version(runtime) {
+        Some(active) if active == installed => {}
+        Some(active) => println!(
+            "`{}` on PATH is {} {}. Use the shims or `wasmenv shell --hook` to run the requested version.",
+            runtime.binary(),
+            runtime.name(),
+            active
+        ),
+        None => println!(
+            "`{}` is not on PATH. Use the shims or `wasmenv shell --hook` to run the requested version.",
+            runtime.binary()
+        ),
+    }
+
+    if !verbose {
+        return Ok(());
+    }
+    let install_dir = runtime.versions_dir()?.join(installed.to_string());
+    println!("Installed at: {}", runtime.bin_dir(&install_dir).display());
+    println!(
+        "Requested by: {} (`{}`)",
+        requested.source, requested.version
+    );
+    println!("Precedence: {}", VERSION_PRECEDENCE);
+    Ok(())
+}
+
+pub fn current(
+    runtime: &dyn Runtime,
+    requested: Option<RequestedVersion>,
+    verbose: bool,
+) -> anyhow::Result<()> {
     verify_wasmenv_is_in_path()?;
-    let current_version = find_current_wasmer();
-    println!("wasmer {}", current_version.unwrap());
+    if let Some(requested) = requested {
+        return current_requested(runtime, requested, verbose);
+    }
+    let Some(current_version) = find_current_version(runtime) else {
+        return Err(anyhow::anyhow!(
+            "No version of {} is in use.\nRun `wasmenv use --runtime {}` to install one.",
+            runtime.name(),
+            runtime.name()
+        ));
+    };
+    println!("{} {}", runtime.name(), current_version);
 
     if !verbose {
         return Ok(());
     }
-    if let Some(path) = find_current_wasmer_dir()?.to_str() {
+    if let Some(path) = find_current_dir(runtime)?.to_str() {
         println!("Installed at: {}", path);
     }
+    println!("Requested by: `wasmenv use`");
+    println!("Precedence: {}", VERSION_PRECEDENCE);
 
     Ok(())
 }
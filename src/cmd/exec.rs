@@ -1,44 +1,214 @@
-use std::{env::temp_dir, path::PathBuf, process::Command};
+use std::{
+    env,
+    path::PathBuf,
+    process::{Command, ExitStatus},
+    str::FromStr,
+};
 
-use semver_eq::VersionReq;
+use anyhow::Context;
+use semver_eq::{Version, VersionReq};
 
-use crate::utils::{
-    download_and_install_wasmer, find_current_wasmer, find_current_wasmer_dir, release_to_install,
-};
+use crate::cmd::install::ensure_installed;
+use crate::config::per_version_cache;
+use crate::runtime::Runtime;
+use crate::utils::{find_current_dir, find_current_version, find_installed_version};
+
+/// Where the version `exec` runs with is installed
+pub struct ExecTarget {
+    /// Directory containing the executables of the version
+    pub bin_dir: PathBuf,
 
-fn setup_exec(version: Option<VersionReq>, install_prerelease: bool) -> anyhow::Result<PathBuf> {
-    let current = find_current_wasmer();
-    let dest_dir = find_current_wasmer_dir()?;
+    /// Installation directory, unless the version is the one found in `PATH`
+    pub install_dir: Option<PathBuf>,
+}
+
+impl ExecTarget {
+    pub fn installed(runtime: &dyn Runtime, version: &Version) -> anyhow::Result<ExecTarget> {
+        let install_dir = runtime.versions_dir()?.join(version.to_string());
+        Ok(ExecTarget {
+            bin_dir: runtime.bin_dir(&install_dir),
+            install_dir: Some(install_dir),
+        })
+    }
 
-    if version.is_none() {
-        return Ok(dest_dir);
+    fn current(runtime: &dyn Runtime) -> anyhow::Result<ExecTarget> {
+        Ok(ExecTarget {
+            bin_dir: find_current_dir(runtime)?,
+            install_dir: None,
+        })
     }
-    let version = version.unwrap();
-    if let Some(current_version) = current {
+}
+
+/// What `exec` runs
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExecCommand {
+    /// Arguments for the runtime's main executable
+    Runtime(Vec<String>),
+    /// Any program with its arguments
+    Program(Vec<String>),
+}
+
+impl ExecCommand {
+    /// Splits the arguments of `exec`. When the version comes first, as in
+    /// `wasmenv exec 4.2 -- cargo build`, the rest is a whole command. Otherwise, as in
+    /// `wasmenv exec -u 4.2 run app.wasm`, the arguments are passed to the runtime.
+    pub fn parse(
+        use_version: Option<VersionReq>,
+        mut command: Vec<String>,
+    ) -> (Option<VersionReq>, ExecCommand) {
+        if use_version.is_none() && command.len() > 1 {
+            if let Ok(version) = VersionReq::from_str(&command[0]) {
+                command.remove(0);
+                if command.first().map(String::as_str) == Some("--") {
+                    command.remove(0);
+                }
+                return (Some(version), ExecCommand::Program(command));
+            }
+        }
+        (use_version, ExecCommand::Runtime(command))
+    }
+}
+
+/// Finds the version to run, installing it into the versions directory if needed. The
+/// `current` version stays as it is.
+pub fn setup_exec(
+    runtime: &dyn Runtime,
+    version: Option<VersionReq>,
+    install_prerelease: bool,
+) -> anyhow::Result<ExecTarget> {
+    let Some(version) = version else {
+        return ExecTarget::current(runtime);
+    };
+    if let Some(installed) = find_installed_version(runtime, &version, install_prerelease)? {
+        return ExecTarget::installed(runtime, &installed);
+    }
+    if let Some(current_version) = find_current_version(runtime) {
         if version.matches(&current_version) {
-            return Ok(dest_dir);
+            return ExecTarget::current(runtime);
         }
     }
-    let dest_dir = temp_dir();
-    if let Some(release) = release_to_install(&Some(version), install_prerelease)? {
-        download_and_install_wasmer(&release, &dest_dir)?;
+    let installed = ensure_installed(runtime, &version, install_prerelease)?;
+    ExecTarget::installed(runtime, &installed)
+}
+
+/// Builds the command to run with the environment of the version: its executables first in
+/// `PATH`, and the runtime's directory variables pointing at it
+pub fn exec_target_command(
+    runtime: &dyn Runtime,
+    target: &ExecTarget,
+    command: &ExecCommand,
+) -> anyhow::Result<Command> {
+    let mut exec_command = match command {
+        ExecCommand::Runtime(args) => {
+            let mut exec_command = Command::new(target.bin_dir.join(runtime.binary()));
+            exec_command.args(args);
+            exec_command
+        }
+        ExecCommand::Program(args) => {
+            let Some((program, args)) = args.split_first() else {
+                return Err(anyhow::anyhow!("No command to run was given"));
+            };
+            let mut exec_command = Command::new(program);
+            exec_command.args(args);
+            exec_command
+        }
     };
 
-    Ok(dest_dir)
+    let mut path = vec![target.bin_dir.clone()];
+    path.extend(env::var_os("PATH").iter().flat_map(env::split_paths));
+    exec_command.env("PATH", env::join_paths(path)?);
+    if let Some(install_dir) = &target.install_dir {
+        if let Some(dir_var) = runtime.dir_var() {
+            exec_command.env(dir_var, install_dir);
+        }
+        if let Some(cache_dir_var) = runtime.cache_dir_var() {
+            if per_version_cache()? {
+                exec_command.env(cache_dir_var, install_dir.join("cache"));
+            }
+        }
+    }
+    Ok(exec_command)
+}
+
+/// Runs `command` in place of wasmenv, so that it gets the terminal, the signals sent to
+/// wasmenv and its exit status is the one of wasmenv. Only returns if the command could not
+/// be started.
+pub fn exec_command(mut command: Command) -> anyhow::Result<()> {
+    let program = command.get_program().to_os_string();
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = command.exec();
+        Err(err).context(format!("Run {:?}", program))
+    }
+    #[cfg(not(unix))]
+    {
+        let status = command.status().context(format!("Run {:?}", program))?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+/// Exit code of a finished command, or 128 plus the number of the signal which killed it,
+/// the way shells report it
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
 }
 
 pub fn exec(
+    runtime: &dyn Runtime,
     version: Option<VersionReq>,
-    command: Vec<String>,
+    command: ExecCommand,
     install_prerelease: bool,
 ) -> anyhow::Result<()> {
-    let dest_dir = setup_exec(version, install_prerelease)?.join("wasmer");
-    let wasmer_exe = dest_dir.to_str().unwrap();
-
-    let output = Command::new(wasmer_exe).args(command).output()?;
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    print!("{}", stdout);
-    eprint!("{}", stderr);
-    Ok(())
+    let target = setup_exec(runtime, version, install_prerelease)?;
+    exec_command(exec_target_command(runtime, &target, &command)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_code() -> anyhow::Result<()> {
+        let status = Command::new("sh").args(["-c", "exit 3"]).status()?;
+        assert_eq!(exit_code(status), 3);
+        let status = Command::new("sh").args(["-c", "kill -TERM $$"]).status()?;
+        assert_eq!(exit_code(status), 143);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_exec_command() {
+        assert_eq!(
+            ExecCommand::parse(None, args(&["4.2", "--", "cargo", "build"])),
+            (
+                Some(VersionReq::from_str("4.2").unwrap()),
+                ExecCommand::Program(args(&["cargo", "build"]))
+            )
+        );
+        assert_eq!(
+            ExecCommand::parse(None, args(&["run", "app.wasm"])),
+            (None, ExecCommand::Runtime(args(&["run", "app.wasm"])))
+        );
+        let version = VersionReq::from_str("4.2").unwrap();
+        assert_eq!(
+            ExecCommand::parse(Some(version.clone()), args(&["4.2", "--version"])),
+            (
+                Some(version),
+                ExecCommand::Runtime(args(&["4.2", "--version"]))
+            )
+        );
+    }
 }
@@ -0,0 +1,68 @@
+use std::env;
+
+use clap::ValueEnum;
+
+use crate::cmd::shell::{env_paths, export_path, export_var, prepend_paths, shell_name, unset_var};
+use crate::cmd::shell_use::shell_path_var;
+use crate::project::find_version_file;
+use crate::runtime::RuntimeKind;
+use crate::utils::{find_installed_version, wasmenv_data_dir};
+
+/// Directories `hook-env` put in front of `PATH`, so they can be taken out again
+pub const HOOK_PATH_VAR: &str = "WASMENV_HOOK_PATH";
+
+/// Prints the shell code putting the versions requested for the current directory on `PATH`.
+/// This runs on every directory change, so it stays away from the network.
+pub fn hook_env(name: Option<String>) -> anyhow::Result<()> {
+    let shell_name = shell_name(name)?;
+    let previous = env_paths(HOOK_PATH_VAR);
+
+    let mut added = Vec::new();
+    let mut wasmer_dir = Some(wasmenv_data_dir()?.join("current"));
+    for kind in RuntimeKind::value_variants() {
+        let runtime = kind.runtime();
+        // a version picked with `wasmenv shell-use` wins over the directory
+        if env::var_os(shell_path_var(runtime)).is_some() {
+            if *kind == RuntimeKind::Wasmer {
+                wasmer_dir = None;
+            }
+            continue;
+        }
+        let Some(requested) = find_version_file(runtime)? else {
+            continue;
+        };
+        let Some(version) =
+            find_installed_version(runtime, &requested.version, requested.prerelease)?
+        else {
+            continue;
+        };
+        let install_dir = runtime.versions_dir()?.join(version.to_string());
+        added.push(runtime.bin_dir(&install_dir));
+        if *kind == RuntimeKind::Wasmer {
+            wasmer_dir = Some(install_dir);
+        }
+    }
+
+    let mut shell_code = vec![export_path(
+        &shell_name,
+        &prepend_paths(&env_paths("PATH"), &previous, &added),
+    )?];
+    if let Some(wasmer_dir) = wasmer_dir {
+        shell_code.push(export_var(
+            &shell_name,
+            "WASMER_DIR",
+            &wasmer_dir.to_string_lossy(),
+        ));
+    }
+    if added.is_empty() {
+        shell_code.push(unset_var(&shell_name, HOOK_PATH_VAR));
+    } else {
+        shell_code.push(export_var(
+            &shell_name,
+            HOOK_PATH_VAR,
+            &env::join_paths(&added)?.to_string_lossy(),
+        ));
+    }
+    println!("{}", shell_code.join("\n"));
+    Ok(())
+}
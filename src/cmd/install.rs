@@ -1,20 +1,22 @@
-use std::{env, fs};
+use std::{env, fs, path::Path};
 
 use anyhow::{self, Context};
 
-use semver_eq::VersionReq;
+use semver_eq::{Version, VersionReq};
 
+use crate::runtime::Runtime;
 use crate::utils::{
-    download_and_install_wasmer, find_current_wasmer, release_to_install,
-    verify_wasmenv_is_in_path, wasmenv_data_dir, Release,
+    download_and_install, find_current_version, find_installed_version, is_installed,
+    release_to_install, verify_wasmenv_is_in_path, wasmenv_data_dir, Release,
 };
 
-fn check_release_already_installed(release: &Release) -> anyhow::Result<()> {
-    let current_version = find_current_wasmer();
+fn check_release_already_installed(runtime: &dyn Runtime, release: &Release) -> anyhow::Result<()> {
+    let current_version = find_current_version(runtime);
     if let Some(current_version) = current_version {
         if release.version() == current_version {
             return Err(anyhow::anyhow!(
-                "You're already using wasmer {}, which is the latest version.",
+                "You're already using {} {}, which is the latest version.",
+                runtime.name(),
                 current_version
             ));
         }
@@ -22,70 +24,133 @@ fn check_release_already_installed(release: &Release) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Points the `current` symlinks at the version of the runtime installed in `version_dir`
+fn link_version(runtime: &dyn Runtime, version_dir: &Path, version: &str) -> anyhow::Result<()> {
+    let runtime_current_dir = wasmenv_data_dir()?.join("current");
+
+    // make sure the current bin directory exists
+    let current_bin_dir = runtime_current_dir.join("bin");
+    fs::create_dir_all(&current_bin_dir)?;
+
+    for binary in runtime.binaries() {
+        let versioned_binary = runtime.bin_dir(version_dir).join(binary);
+        let current_binary = current_bin_dir.join(binary);
+        // a link to a binary this version doesn't have would keep running the previous one
+        if current_binary.symlink_metadata().is_ok() {
+            fs::remove_file(&current_binary)?;
+        }
+        if !versioned_binary.exists() {
+            continue;
+        }
+        symlink::symlink_file(&versioned_binary, &current_binary)?;
+
+        let binary_versioned_path = current_bin_dir.join(format!("{binary}{version}"));
+        if binary_versioned_path.symlink_metadata().is_ok() {
+            fs::remove_file(&binary_versioned_path).context("Removing versioned path")?;
+        }
+        symlink::symlink_file(&versioned_binary, binary_versioned_path)?;
+    }
+
+    env::set_var("WASMER_DIR", &runtime_current_dir);
+    Ok(())
+}
+
+/// Installs the newest release matching `version` into the versions directory unless it is
+/// installed already, without switching the `current` version to it
+pub fn ensure_installed(
+    runtime: &dyn Runtime,
+    version: &VersionReq,
+    install_prerelease: bool,
+) -> anyhow::Result<Version> {
+    let Some(release) = release_to_install(runtime, &Some(version.clone()), install_prerelease)?
+    else {
+        return Err(anyhow::anyhow!(
+            "{} release `{}` was not found.",
+            runtime.name(),
+            version
+        ));
+    };
+    ensure_release_installed(runtime, &release)?;
+    Ok(release.version())
+}
+
+/// Installs a release into the versions directory unless it is installed already, without
+/// switching the `current` version to it
+pub fn ensure_release_installed(runtime: &dyn Runtime, release: &Release) -> anyhow::Result<()> {
+    let installed = release.version();
+    if is_installed(runtime, &installed) {
+        return Ok(());
+    }
+
+    // unpack next to the final directory and move it in place at once, so that concurrent
+    // runs never see a half unpacked version
+    let versions_dir = runtime.versions_dir()?;
+    fs::create_dir_all(&versions_dir).context(format!("Create {:?}", versions_dir))?;
+    let unpack_dir = tempfile::Builder::new()
+        .prefix(&format!(".{installed}-"))
+        .tempdir_in(&versions_dir)?;
+    download_and_install(runtime, release, &unpack_dir.path().to_path_buf())?;
+    let version_dir = versions_dir.join(installed.to_string());
+    if version_dir.exists() && !is_installed(runtime, &installed) {
+        fs::remove_dir_all(&version_dir).context(format!("Remove {:?}", version_dir))?;
+    }
+    if let Err(err) = fs::rename(unpack_dir.path(), &version_dir) {
+        // another run installed the same version in the meantime
+        if !is_installed(runtime, &installed) {
+            return Err(err).context(format!("Move the unpacked release to {:?}", version_dir));
+        }
+    }
+    Ok(())
+}
+
 fn install_version(
+    runtime: &dyn Runtime,
     version: Option<VersionReq>,
     install_prerelease: bool,
-) -> anyhow::Result<Release> {
+    remote: bool,
+) -> anyhow::Result<Version> {
     verify_wasmenv_is_in_path()?;
 
-    let release = match release_to_install(&version, install_prerelease)? {
+    if let (Some(req), false) = (&version, remote) {
+        if let Some(installed) = find_installed_version(runtime, req, install_prerelease)? {
+            let version_dir = runtime.versions_dir()?.join(installed.to_string());
+            link_version(runtime, &version_dir, &installed.to_string())?;
+            return Ok(installed);
+        }
+    }
+
+    let release = match release_to_install(runtime, &version, install_prerelease)? {
         Some(rel) => rel,
         None => {
             return Err(anyhow::anyhow!(
-                "Wasmer release `{}` was not found.\nIf you are trying to install a prerelease version, try passing the `--prerelease` flag.",
+                "{} release `{}` was not found.\nIf you are trying to install a prerelease version, try passing the `--prerelease` flag.",
+                runtime.name(),
                 version.unwrap()
             ))
         }
     };
-    check_release_already_installed(&release)?;
+    check_release_already_installed(runtime, &release)?;
 
-    let data_dir = wasmenv_data_dir().expect("Getting wasmenv data directory");
+    ensure_release_installed(runtime, &release)?;
     let version = release.version().to_string();
-    let wasmer_dir_path = &version;
-    let wasmer_version_dir = data_dir.join(wasmer_dir_path);
-    let wasmer_current_dir = data_dir.join("current");
-    let wasmer_old_dir = data_dir.join(".wasmenv/old");
-
-    if download_and_install_wasmer(&release, &wasmer_version_dir).is_err()
-        && wasmer_version_dir.exists()
-        && wasmer_old_dir.exists()
-    {
-        fs::rename(&wasmer_old_dir, &wasmer_current_dir)?;
-        println!("Failed to install wasmer. Reverting back to the old version.");
-    };
-
-    let current_wasmer = &wasmer_current_dir.join("bin/wasmer");
-    let versioned_wasmer = &wasmer_version_dir.join("bin/wasmer");
-
-    if current_wasmer.exists() {
-        fs::remove_file(current_wasmer)?;
-    }
-
-    fs::create_dir_all(&wasmer_current_dir)?;
-
-    // make sure the current wasmer directory exists
-    let parent_dir = current_wasmer
-        .parent()
-        .context("Find the parent of current wasmer dir")?;
-    fs::create_dir_all(parent_dir)?;
-    symlink::symlink_file(versioned_wasmer, current_wasmer)?;
-
-    let wasmer_versioned_path = wasmer_current_dir.join(format!("bin/wasmer{version}"));
-    if wasmer_versioned_path.exists() {
-        fs::remove_file(&wasmer_versioned_path).context("Removing versioned path")?;
-    }
-
-    symlink::symlink_file(versioned_wasmer, wasmer_versioned_path)?;
-    env::set_var("WASMER_DIR", &wasmer_current_dir);
-    Ok(release)
+    let runtime_version_dir = runtime.versions_dir()?.join(&version);
+    link_version(runtime, &runtime_version_dir, &version)?;
+    Ok(release.version())
 }
 
-pub fn install(version: Option<VersionReq>, install_prerelease: bool) -> anyhow::Result<()> {
-    let release = install_version(version, install_prerelease)?;
+pub fn install(
+    runtime: &dyn Runtime,
+    version: Option<VersionReq>,
+    install_prerelease: bool,
+    remote: bool,
+) -> anyhow::Result<()> {
+    let version = install_version(runtime, version, install_prerelease, remote)?;
     println!(
-                "You are now using wasmer {}. You can run `wasmer --version` to check your version of wasmer.",
-                release.version()
-            );
+        "You are now using {0} {1}. You can run `{2} --version` to check your version of {0}.",
+        runtime.name(),
+        version,
+        runtime.binary()
+    );
 
     Ok(())
 }
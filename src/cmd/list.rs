@@ -1,12 +1,91 @@
+use std::{fs, path::Path};
+
+use chrono::{DateTime, Local};
+use indicatif::BinaryBytes;
 use semver_eq::VersionReq;
 
 use comfy_table::{Attribute, Cell, Color, Table};
 
-use crate::utils::list_releases_interactively;
+use crate::runtime::Runtime;
+use crate::utils::{find_linked_version, installed_versions, list_releases_interactively};
+
+/// Total size of the files in a directory, not following symlinks
+fn size_on_disk(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| size_on_disk(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn list_installed(runtime: &dyn Runtime, version: Option<VersionReq>) -> anyhow::Result<()> {
+    let versions_dir = runtime.versions_dir()?;
+    let linked_version = find_linked_version(runtime);
+    let mut table = Table::new();
+    table.set_header(vec![
+        Cell::new("Tags").add_attribute(Attribute::Bold),
+        Cell::new("Version").add_attribute(Attribute::Bold),
+        Cell::new("Installed at").add_attribute(Attribute::Bold),
+        Cell::new("Size").add_attribute(Attribute::Bold),
+    ]);
+
+    let mut installed = installed_versions(runtime)?;
+    installed.reverse();
+    for installed_version in installed {
+        if let Some(ref version) = version {
+            if !version.matches(&installed_version) {
+                continue;
+            }
+        }
+        let install_dir = versions_dir.join(installed_version.to_string());
+        let installed_at = fs::metadata(&install_dir)
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| {
+                DateTime::<Local>::from(modified)
+                    .format("%B %e %Y %r")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let mut tags = Vec::new();
+        if !installed_version.pre.is_empty() {
+            tags.push("prerelease");
+        }
+        if linked_version.as_ref() == Some(&installed_version) {
+            tags.push("current");
+        }
+        table.add_row(vec![
+            Cell::new(tags.join(", "))
+                .fg(Color::Yellow)
+                .add_attribute(Attribute::Italic),
+            Cell::new(installed_version).add_attribute(Attribute::Bold),
+            Cell::new(installed_at),
+            Cell::new(BinaryBytes(size_on_disk(&install_dir))),
+        ]);
+    }
+    println!("{table}");
+    Ok(())
+}
 
-pub fn list(version: Option<VersionReq>, count: Option<usize>, all: bool) -> anyhow::Result<()> {
-    let mut releases =
-        list_releases_interactively().expect("A list of wasmer versions from github.");
+pub fn list(
+    runtime: &dyn Runtime,
+    version: Option<VersionReq>,
+    count: Option<usize>,
+    all: bool,
+    installed: bool,
+) -> anyhow::Result<()> {
+    if installed {
+        return list_installed(runtime, version);
+    }
+    let mut releases = list_releases_interactively(runtime)?;
     let mut table = Table::new();
     table.set_header(vec![
         Cell::new("Tags").add_attribute(Attribute::Bold),
@@ -33,7 +112,7 @@ pub fn list(version: Option<VersionReq>, count: Option<usize>, all: bool) -> any
             }
         }
         table.add_row(vec![
-            Cell::new(release.tags().join(", "))
+            Cell::new(release.tags(runtime).join(", "))
                 .fg(Color::Yellow)
                 .add_attribute(Attribute::Italic),
             Cell::new(release_version).add_attribute(Attribute::Bold),
@@ -43,3 +122,19 @@ pub fn list(version: Option<VersionReq>, count: Option<usize>, all: bool) -> any
     println!("{table}");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_on_disk() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("bin"))?;
+        fs::write(dir.path().join("bin/wasmer"), [0; 100])?;
+        fs::write(dir.path().join("LICENSE"), [0; 20])?;
+        assert_eq!(size_on_disk(dir.path()), 120);
+        assert_eq!(size_on_disk(&dir.path().join("missing")), 0);
+        Ok(())
+    }
+}
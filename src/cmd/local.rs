@@ -0,0 +1,45 @@
+use std::{env, fs};
+
+use anyhow::Context;
+use semver_eq::{Version, VersionReq};
+
+use crate::project::{set_tool_version, TOOL_VERSIONS_FILE};
+use crate::runtime::Runtime;
+use crate::utils::{find_installed_version, release_to_install};
+
+/// Resolves a version requirement to the exact version to pin, as asdf and mise only install
+/// exact versions. An installed version is preferred over the newest release.
+fn version_to_pin(runtime: &dyn Runtime, version: &VersionReq) -> anyhow::Result<Version> {
+    if let Some(installed) = find_installed_version(runtime, version, false)? {
+        return Ok(installed);
+    }
+    match release_to_install(runtime, &Some(version.clone()), false)? {
+        Some(release) => Ok(release.version()),
+        None => Err(anyhow::anyhow!(
+            "{} release `{}` was not found.",
+            runtime.name(),
+            version
+        )),
+    }
+}
+
+/// Pins a version of the runtime for the current directory in its `.tool-versions` file
+pub fn local(runtime: &dyn Runtime, version: VersionReq) -> anyhow::Result<()> {
+    let version = version_to_pin(runtime, &version)?.to_string();
+
+    let path = env::current_dir()?.join(TOOL_VERSIONS_FILE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).context(format!("Read {:?}", path)),
+    };
+    fs::write(&path, set_tool_version(&contents, runtime.name(), &version))
+        .context(format!("Write {:?}", path))?;
+    println!(
+        "Pinned {} {} in {}",
+        runtime.name(),
+        version,
+        path.display()
+    );
+    Ok(())
+}
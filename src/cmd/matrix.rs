@@ -0,0 +1,169 @@
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use comfy_table::{Attribute, Cell, Color, Table};
+use semver_eq::VersionReq;
+use serde::Serialize;
+
+use crate::cmd::exec::{exec_target_command, exit_code, ExecCommand, ExecTarget};
+use crate::cmd::install::ensure_release_installed;
+use crate::runtime::Runtime;
+use crate::utils::{releases_to_install, verify_wasmenv_is_in_path, Release};
+
+/// Outcome of running the command with one version
+#[derive(Debug, Serialize)]
+struct MatrixRun {
+    version: String,
+    exit_code: i32,
+    duration_secs: f64,
+}
+
+/// Exit code shells use for a command which could not be found or started
+const COMMAND_NOT_RUN: i32 = 127;
+
+/// Runs the command with the version of a release and shows what it printed once it is done,
+/// so that the output of parallel runs doesn't get mixed up
+fn run_release(
+    runtime: &dyn Runtime,
+    release: &Release,
+    command: &ExecCommand,
+) -> anyhow::Result<MatrixRun> {
+    let version = release.version();
+    let target = ExecTarget::installed(runtime, &version)?;
+    let mut exec_command = exec_target_command(runtime, &target, command)?;
+
+    let started = Instant::now();
+    let output = exec_command.output();
+    let duration = started.elapsed();
+
+    let mut stderr = io::stderr().lock();
+    let exit_code = match output {
+        Ok(output) => {
+            let exit_code = exit_code(output.status);
+            writeln!(
+                stderr,
+                "==> {} {} (exit code {})",
+                runtime.name(),
+                version,
+                exit_code
+            )?;
+            stderr.write_all(&output.stdout)?;
+            stderr.write_all(&output.stderr)?;
+            exit_code
+        }
+        Err(err) => {
+            writeln!(stderr, "==> {} {}: {}", runtime.name(), version, err)?;
+            COMMAND_NOT_RUN
+        }
+    };
+    Ok(MatrixRun {
+        version: version.to_string(),
+        exit_code,
+        duration_secs: duration.as_secs_f64(),
+    })
+}
+
+fn print_table(runtime: &dyn Runtime, runs: &[MatrixRun]) {
+    let mut table = Table::new();
+    table.set_header(vec![
+        Cell::new(runtime.name()).add_attribute(Attribute::Bold),
+        Cell::new("Result").add_attribute(Attribute::Bold),
+        Cell::new("Exit code").add_attribute(Attribute::Bold),
+        Cell::new("Duration").add_attribute(Attribute::Bold),
+    ]);
+    for run in runs {
+        let result = if run.exit_code == 0 {
+            Cell::new("passed").fg(Color::Green)
+        } else {
+            Cell::new("failed").fg(Color::Red)
+        };
+        table.add_row(vec![
+            Cell::new(&run.version).add_attribute(Attribute::Bold),
+            result,
+            Cell::new(run.exit_code),
+            Cell::new(format!(
+                "{:.2?}",
+                Duration::from_secs_f64(run.duration_secs)
+            )),
+        ]);
+    }
+    println!("{table}");
+}
+
+/// Runs a command with every release matching `version`, `jobs` at a time, and sums up how
+/// each of them went
+pub fn matrix(
+    runtime: &dyn Runtime,
+    version: VersionReq,
+    command: Vec<String>,
+    install_prerelease: bool,
+    jobs: usize,
+    json: bool,
+) -> anyhow::Result<()> {
+    verify_wasmenv_is_in_path()?;
+    if command.is_empty() {
+        return Err(anyhow::anyhow!("No command to run was given"));
+    }
+    let releases: Vec<Release> =
+        releases_to_install(runtime, &Some(version.clone()), install_prerelease)?
+            .into_iter()
+            .filter(|release| release.asset(runtime).is_some())
+            .collect();
+    if releases.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No {} release matches `{}`.",
+            runtime.name(),
+            version
+        ));
+    }
+    // installing one version at a time keeps the progress bars readable
+    for release in &releases {
+        ensure_release_installed(runtime, release)?;
+    }
+
+    let command = ExecCommand::Program(command);
+    let next = AtomicUsize::new(0);
+    let runs = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(release) = releases.get(index) else {
+                    break;
+                };
+                let run = run_release(runtime, release, &command);
+                runs.lock().unwrap().push((index, run));
+            });
+        }
+    });
+    // report the runs in the order of the releases, newest first
+    let mut runs = runs.into_inner().unwrap();
+    runs.sort_by_key(|(index, _)| *index);
+    let runs = runs
+        .into_iter()
+        .map(|(_, run)| run)
+        .collect::<anyhow::Result<Vec<MatrixRun>>>()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&runs)?);
+    } else {
+        print_table(runtime, &runs);
+    }
+    let failed = runs.iter().filter(|run| run.exit_code != 0).count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "The command failed with {} of {} {} versions.",
+            failed,
+            runs.len(),
+            runtime.name()
+        ));
+    }
+    Ok(())
+}
@@ -1,6 +1,13 @@
+pub mod bisect;
 pub mod clear_cache;
 pub mod current;
 pub mod exec;
+pub mod hook_env;
 pub mod install;
 pub mod list;
+pub mod local;
+pub mod matrix;
 pub mod shell;
+pub mod shell_use;
+pub mod shims;
+pub mod uninstall;
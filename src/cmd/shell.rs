@@ -4,8 +4,9 @@ use anyhow::anyhow;
 
 use crate::utils::wasmenv_config_dir;
 
-pub fn shell(name: Option<String>) -> anyhow::Result<()> {
-    let env_shell = env::var("SHELL").unwrap();
+/// Name of the shell to configure, from `name` or `$SHELL`
+pub fn shell_name(name: Option<String>) -> anyhow::Result<String> {
+    let env_shell = env::var("SHELL").unwrap_or_default();
     let shell_path = match name {
         Some(ref shell) => shell,
         None => &env_shell,
@@ -15,9 +16,111 @@ pub fn shell(name: Option<String>) -> anyhow::Result<()> {
         .and_then(|os_str| os_str.to_str())
         .ok_or(anyhow!("Invalid shell name"))?
         .to_owned();
+    match shell_name.as_str() {
+        "bash" | "zsh" | "fish" => Ok(shell_name),
+        _ => Err(anyhow!(format!(
+            "Shell `{}` not recognized. Try one of `bash`, `zsh` or `fish`",
+            shell_name
+        ))),
+    }
+}
+
+/// Quotes a value so that the shell reads it back unchanged
+fn quote(shell_name: &str, value: &str) -> String {
+    match shell_name {
+        "fish" => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+        _ => format!("'{}'", value.replace('\'', r#"'\''"#)),
+    }
+}
+
+/// Shell code setting an environment variable
+pub fn export_var(shell_name: &str, name: &str, value: &str) -> String {
+    match shell_name {
+        "fish" => format!("set -gx {} {};", name, quote(shell_name, value)),
+        _ => format!("export {}={};", name, quote(shell_name, value)),
+    }
+}
+
+/// Shell code unsetting an environment variable
+pub fn unset_var(shell_name: &str, name: &str) -> String {
+    match shell_name {
+        "fish" => format!("set -e {};", name),
+        _ => format!("unset {};", name),
+    }
+}
+
+/// Shell code setting `PATH` to `paths`. fish keeps `PATH` as a list, so every entry is
+/// passed separately.
+pub fn export_path(shell_name: &str, paths: &[PathBuf]) -> anyhow::Result<String> {
+    Ok(match shell_name {
+        "fish" => {
+            let paths: Vec<String> = paths
+                .iter()
+                .map(|path| quote(shell_name, &path.to_string_lossy()))
+                .collect();
+            format!("set -gx PATH {};", paths.join(" "))
+        }
+        _ => export_var(
+            shell_name,
+            "PATH",
+            &env::join_paths(paths)?.to_string_lossy(),
+        ),
+    })
+}
+
+/// Directories listed in an environment variable such as `PATH`
+pub fn env_paths(var: &str) -> Vec<PathBuf> {
+    env::var_os(var)
+        .map(|paths| env::split_paths(&paths).collect())
+        .unwrap_or_default()
+}
+
+/// `path` without the `removed` directories, which wasmenv added before, and with `added`
+/// in front
+pub fn prepend_paths(path: &[PathBuf], removed: &[PathBuf], added: &[PathBuf]) -> Vec<PathBuf> {
+    added
+        .iter()
+        .chain(path.iter().filter(|dir| !removed.contains(dir)))
+        .cloned()
+        .collect()
+}
+
+/// Code which runs `wasmenv hook-env` whenever the directory changes
+fn hook_code(shell_name: &str) -> String {
+    match shell_name {
+        "bash" => r#"
+_wasmenv_hook() {
+  eval "$(wasmenv hook-env bash < /dev/null)"
+}
+if [[ ";${PROMPT_COMMAND[*]:-};" != *";_wasmenv_hook;"* ]]; then
+  PROMPT_COMMAND="_wasmenv_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+"#
+        .to_string(),
+        "zsh" => r#"
+_wasmenv_hook() {
+  eval "$(wasmenv hook-env zsh < /dev/null)"
+}
+autoload -U add-zsh-hook
+add-zsh-hook chpwd _wasmenv_hook
+_wasmenv_hook
+"#
+        .to_string(),
+        _ => r#"
+function _wasmenv_hook --on-variable PWD
+  wasmenv hook-env fish < /dev/null | source
+end
+_wasmenv_hook
+"#
+        .to_string(),
+    }
+}
+
+pub fn shell(name: Option<String>, hook: bool) -> anyhow::Result<()> {
+    let shell_name = shell_name(name)?;
     let wasmenv_dir = wasmenv_config_dir()?;
     let wasmenv_dir = wasmenv_dir.to_str().unwrap();
-    let shell_code = match shell_name.as_str() {
+    let mut shell_code = match shell_name.as_str() {
         "bash" | "zsh" => format!(
             r#"
 # {shell_name} config for wasmenv
@@ -27,7 +130,7 @@ export WASMENV_DIR="{0}"
 "#,
             wasmenv_dir
         ),
-        "fish" => format!(
+        _ => format!(
             r#"
 # {shell_name} config for wasmenv
 # Copy this to ~/.config/fish/config.fish
@@ -36,15 +139,49 @@ set -x PATH $WASMER_DIR/bin $PATH
 "#,
             wasmenv_dir
         ),
-        _ => {
-            return Err(anyhow!(format!(
-                "Shell `{}` not recognized. Try one of `bash`, `zsh` or `fish`",
-                shell_name
-            )));
-        }
     };
+    if hook {
+        shell_code.push_str(&hook_code(&shell_name));
+    }
     println!("{}", shell_code);
     // let mut stdout = stdout();
     // stdout.write_all(shell_code.as_bytes())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepend_paths() {
+        let path = vec![
+            PathBuf::from("/data/wasmenv/4.2.0/bin"),
+            PathBuf::from("/data/wasmenv/current/bin"),
+            PathBuf::from("/usr/bin"),
+        ];
+        let removed = vec![PathBuf::from("/data/wasmenv/4.2.0/bin")];
+        let added = vec![PathBuf::from("/data/wasmenv/3.0.0/bin")];
+        assert_eq!(
+            prepend_paths(&path, &removed, &added),
+            vec![
+                PathBuf::from("/data/wasmenv/3.0.0/bin"),
+                PathBuf::from("/data/wasmenv/current/bin"),
+                PathBuf::from("/usr/bin"),
+            ]
+        );
+        assert_eq!(prepend_paths(&path, &removed, &[]), path[1..].to_vec());
+    }
+
+    #[test]
+    fn test_export_var() {
+        assert_eq!(
+            export_var("bash", "WASMER_DIR", "/home/o'neil/wasmer"),
+            r#"export WASMER_DIR='/home/o'\''neil/wasmer';"#
+        );
+        assert_eq!(
+            export_var("fish", "WASMER_DIR", "/home/o'neil/wasmer"),
+            r#"set -gx WASMER_DIR '/home/o\'neil/wasmer';"#
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    env,
+    path::{Component, Path, PathBuf},
+};
+
+use semver_eq::{Version, VersionReq};
+
+use crate::cmd::hook_env::HOOK_PATH_VAR;
+use crate::cmd::install::ensure_installed;
+use crate::cmd::shell::{env_paths, export_path, export_var, prepend_paths, shell_name, unset_var};
+use crate::runtime::Runtime;
+use crate::utils::{find_installed_version, wasmenv_data_dir};
+
+/// Variable holding the directory `shell-use` put in front of `PATH` for a runtime, e.g.
+/// `WASMENV_SHELL_PATH_WASMER`, so that it can be taken out again without touching the
+/// versions picked for other runtimes
+pub fn shell_path_var(runtime: &dyn Runtime) -> String {
+    format!("WASMENV_SHELL_PATH_{}", runtime.name().to_uppercase())
+}
+
+/// Whether `path` is the bin directory of a version of the runtime installed in
+/// `versions_dir`. wasmer keeps its versions next to the directories of other runtimes, so
+/// the directory under `versions_dir` has to be a version.
+fn is_version_bin_dir(runtime: &dyn Runtime, versions_dir: &Path, path: &Path) -> bool {
+    let Ok(relative_path) = path.strip_prefix(versions_dir) else {
+        return false;
+    };
+    let Some(Component::Normal(version)) = relative_path.components().next() else {
+        return false;
+    };
+    let is_version = version
+        .to_str()
+        .is_some_and(|version| Version::parse(version).is_ok());
+    is_version && runtime.bin_dir(&versions_dir.join(version)) == path
+}
+
+/// Prints the shell code switching the current shell session to a version of the runtime,
+/// installing it first if needed. With `unset`, the code switches the runtime back to the
+/// `current` version instead.
+pub fn shell_use(
+    runtime: &dyn Runtime,
+    version: Option<VersionReq>,
+    name: Option<String>,
+    install_prerelease: bool,
+    unset: bool,
+) -> anyhow::Result<()> {
+    let shell_name = shell_name(name)?;
+    let shell_path_var = shell_path_var(runtime);
+    let versions_dir = runtime.versions_dir()?;
+
+    // only the directories of this runtime are taken out, whether `shell-use` or the hook
+    // put them there
+    let (hook_removed, hook_kept): (Vec<PathBuf>, Vec<PathBuf>) = env_paths(HOOK_PATH_VAR)
+        .into_iter()
+        .partition(|path| is_version_bin_dir(runtime, &versions_dir, path));
+    let mut removed = env_paths(&shell_path_var);
+    removed.extend(hook_removed);
+    let path = env_paths("PATH");
+
+    let mut shell_code = match version {
+        Some(version) if !unset => {
+            let version = match find_installed_version(runtime, &version, install_prerelease)? {
+                Some(installed) => installed,
+                None => ensure_installed(runtime, &version, install_prerelease)?,
+            };
+            let install_dir = versions_dir.join(version.to_string());
+            let added = vec![runtime.bin_dir(&install_dir)];
+            let mut shell_code = vec![
+                export_path(&shell_name, &prepend_paths(&path, &removed, &added))?,
+                export_var(
+                    &shell_name,
+                    &shell_path_var,
+                    &env::join_paths(&added)?.to_string_lossy(),
+                ),
+            ];
+            if let Some(dir_var) = runtime.dir_var() {
+                shell_code.push(export_var(
+                    &shell_name,
+                    dir_var,
+                    &install_dir.to_string_lossy(),
+                ));
+            }
+            shell_code
+        }
+        _ => {
+            let mut shell_code = vec![
+                export_path(&shell_name, &prepend_paths(&path, &removed, &[]))?,
+                unset_var(&shell_name, &shell_path_var),
+            ];
+            if let Some(dir_var) = runtime.dir_var() {
+                shell_code.push(export_var(
+                    &shell_name,
+                    dir_var,
+                    &wasmenv_data_dir()?.join("current").to_string_lossy(),
+                ));
+            }
+            shell_code
+        }
+    };
+    if hook_kept.is_empty() {
+        shell_code.push(unset_var(&shell_name, HOOK_PATH_VAR));
+    } else {
+        shell_code.push(export_var(
+            &shell_name,
+            HOOK_PATH_VAR,
+            &env::join_paths(&hook_kept)?.to_string_lossy(),
+        ));
+    }
+    println!("{}", shell_code.join("\n"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{Wasmer, Wasmtime};
+
+    #[test]
+    fn test_is_version_bin_dir() {
+        let data_dir = Path::new("/data/wasmenv");
+        let wasmtime_dir = data_dir.join("wasmtime");
+        assert!(is_version_bin_dir(
+            &Wasmer,
+            data_dir,
+            &data_dir.join("4.2.0/bin")
+        ));
+        assert!(!is_version_bin_dir(
+            &Wasmer,
+            data_dir,
+            &wasmtime_dir.join("14.0.4")
+        ));
+        assert!(!is_version_bin_dir(
+            &Wasmer,
+            data_dir,
+            &data_dir.join("current/bin")
+        ));
+        assert!(is_version_bin_dir(
+            &Wasmtime,
+            &wasmtime_dir,
+            &wasmtime_dir.join("14.0.4")
+        ));
+        assert_eq!(shell_path_var(&Wasmtime), "WASMENV_SHELL_PATH_WASMTIME");
+    }
+}
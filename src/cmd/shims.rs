@@ -0,0 +1,102 @@
+use std::{ffi::OsString, fs, path::Path, process::Command};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use semver_eq::VersionReq;
+
+use crate::cmd::exec::exec_command;
+use crate::cmd::install::ensure_installed;
+use crate::config::auto_install;
+use crate::project::requested_version;
+use crate::runtime::{Runtime, RuntimeKind};
+use crate::utils::{find_installed_version, wasmenv_data_dir};
+
+/// Finds the runtime a shim stands in for from the name wasmenv was invoked as, e.g.
+/// `wasmer` or `/home/user/.local/share/wasmenv/shims/wasmtime`
+pub fn shim_target(argv0: &Path) -> Option<(&'static dyn Runtime, &'static str)> {
+    let name = argv0.file_stem()?.to_str()?;
+    RuntimeKind::value_variants().iter().find_map(|kind| {
+        let runtime = kind.runtime();
+        let binary = runtime.binaries().iter().find(|binary| **binary == name)?;
+        Some((runtime, *binary))
+    })
+}
+
+/// Runs `binary` of the version of the runtime requested for the current directory, or the
+/// `current` version if nothing is requested
+pub fn run_shim(
+    runtime: &dyn Runtime,
+    binary: &str,
+    args: Vec<OsString>,
+    env_version: Option<VersionReq>,
+) -> anyhow::Result<()> {
+    let executable = match requested_version(runtime, None, None, env_version)? {
+        Some(requested) => {
+            let installed =
+                find_installed_version(runtime, &requested.version, requested.prerelease)?;
+            let version = match installed {
+                Some(version) => version,
+                None if auto_install()? => {
+                    ensure_installed(runtime, &requested.version, requested.prerelease)?
+                }
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "{} `{}` requested by {} is not installed.\nRun `wasmenv use` to install it, or set WASMENV_AUTO_INSTALL=1.",
+                        runtime.name(),
+                        requested.version,
+                        requested.source
+                    ))
+                }
+            };
+            let install_dir = runtime.versions_dir()?.join(version.to_string());
+            runtime.bin_dir(&install_dir).join(binary)
+        }
+        None => wasmenv_data_dir()?.join("current/bin").join(binary),
+    };
+    if !executable.exists() {
+        return Err(anyhow::anyhow!(
+            "No version of {} is in use.\nRun `wasmenv use` to pick one.",
+            runtime.name()
+        ));
+    }
+
+    let mut command = Command::new(&executable);
+    command.args(args);
+    exec_command(command)
+}
+
+/// Creates a shim for every runtime binary in the shims directory, pointing at this wasmenv
+pub fn shims() -> anyhow::Result<()> {
+    let shims_dir = wasmenv_data_dir()?.join("shims");
+    fs::create_dir_all(&shims_dir).context(format!("Create {:?}", shims_dir))?;
+    let wasmenv = std::env::current_exe().context("Finding the wasmenv executable")?;
+
+    for kind in RuntimeKind::value_variants() {
+        for binary in kind.runtime().binaries() {
+            let shim = shims_dir.join(binary);
+            if shim.symlink_metadata().is_ok() {
+                fs::remove_file(&shim)?;
+            }
+            symlink::symlink_file(&wasmenv, &shim).context(format!("Create {:?}", shim))?;
+        }
+    }
+    println!(
+        "Created shims in {0}\nAdd it to the front of your PATH to pick the version per directory:\nexport PATH=\"{0}:$PATH\"",
+        shims_dir.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shim_target() {
+        let (runtime, binary) = shim_target(Path::new("/data/wasmenv/shims/wasmer")).unwrap();
+        assert_eq!((runtime.name(), binary), ("wasmer", "wasmer"));
+        let (runtime, binary) = shim_target(Path::new("wasmtime-min")).unwrap();
+        assert_eq!((runtime.name(), binary), ("wasmtime", "wasmtime-min"));
+        assert!(shim_target(Path::new("/usr/bin/wasmenv")).is_none());
+    }
+}
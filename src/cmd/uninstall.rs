@@ -0,0 +1,96 @@
+use std::{
+    env::consts::{ARCH, OS},
+    fs,
+};
+
+use anyhow::Context;
+use semver_eq::{Version, VersionReq};
+
+use crate::checksum::remove_download;
+use crate::runtime::Runtime;
+use crate::utils::{
+    find_linked_version, installed_versions, verify_wasmenv_is_in_path, wasmenv_cache_dir,
+    wasmenv_data_dir,
+};
+
+fn remove_version(runtime: &dyn Runtime, version: &Version, purge: bool) -> anyhow::Result<()> {
+    let version_dir = runtime.versions_dir()?.join(version.to_string());
+    fs::remove_dir_all(&version_dir).context(format!("Remove {:?}", version_dir))?;
+
+    let current_bin_dir = wasmenv_data_dir()?.join("current/bin");
+    for binary in runtime.binaries() {
+        let versioned_binary = current_bin_dir.join(format!("{binary}{version}"));
+        if versioned_binary.symlink_metadata().is_ok() {
+            fs::remove_file(&versioned_binary).context("Removing versioned path")?;
+        }
+    }
+
+    if purge {
+        if let Some(asset_name) = runtime.asset_name(OS, ARCH, version) {
+            let download = wasmenv_cache_dir()?.join(format!("{version}-{asset_name}"));
+            if download.exists() {
+                remove_download(&download)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes the `current` symlinks of the runtime, which would dangle once the version
+/// they point at is gone
+fn remove_current_links(runtime: &dyn Runtime) -> anyhow::Result<()> {
+    let current_bin_dir = wasmenv_data_dir()?.join("current/bin");
+    for binary in runtime.binaries() {
+        let current_binary = current_bin_dir.join(binary);
+        if current_binary.symlink_metadata().is_ok() {
+            fs::remove_file(&current_binary)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn uninstall(
+    runtime: &dyn Runtime,
+    version: VersionReq,
+    force: bool,
+    purge: bool,
+) -> anyhow::Result<()> {
+    verify_wasmenv_is_in_path()?;
+
+    let versions: Vec<Version> = installed_versions(runtime)?
+        .into_iter()
+        .filter(|installed| version.matches(installed))
+        .collect();
+    if versions.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No installed {} version matches `{}`.",
+            runtime.name(),
+            version
+        ));
+    }
+
+    let linked_version = find_linked_version(runtime);
+    let removes_current = linked_version
+        .as_ref()
+        .is_some_and(|linked| versions.contains(linked));
+    if removes_current && !force {
+        return Err(anyhow::anyhow!(
+            "{} {} is the version currently in use.\nPass `--force` to uninstall it anyway.",
+            runtime.name(),
+            linked_version.unwrap()
+        ));
+    }
+
+    for version in &versions {
+        remove_version(runtime, version, purge)?;
+        println!("Uninstalled {} {}", runtime.name(), version);
+    }
+    if removes_current {
+        remove_current_links(runtime)?;
+        println!(
+            "No version of {} is in use anymore. Run `wasmenv use` to pick one.",
+            runtime.name()
+        );
+    }
+    Ok(())
+}
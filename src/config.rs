@@ -0,0 +1,259 @@
+use std::{env, fs, path::PathBuf, str::FromStr, sync::OnceLock, time::Duration};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::utils::wasmenv_config_dir;
+
+/// Settings read from `config.toml` in the wasmenv config directory
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Token used to authenticate requests to the GitHub API
+    pub github_token: Option<String>,
+
+    /// Wait for the GitHub API rate limit to reset instead of failing
+    pub wait_on_rate_limit: bool,
+
+    /// Base URL of a server speaking the GitHub releases API, used instead of GitHub
+    pub release_source: Option<String>,
+
+    /// Base URL replacing `https://github.com` in release asset download URLs
+    pub asset_mirror: Option<String>,
+
+    /// How many times a failed request is tried again
+    pub http_retries: Option<u32>,
+
+    /// Seconds to wait for a connection to a server
+    pub connect_timeout: Option<u64>,
+
+    /// Seconds to wait for a server to send data
+    pub read_timeout: Option<u64>,
+
+    /// Proxy for all requests, used instead of `HTTPS_PROXY`/`HTTP_PROXY`
+    pub proxy: Option<String>,
+
+    /// PEM file with extra certificate authorities to trust
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Trust the certificate authorities bundled with wasmenv instead of the ones of the
+    /// operating system
+    pub bundled_certs: bool,
+
+    /// Let shims install the version a project asks for when it is missing
+    pub auto_install: bool,
+
+    /// Give every version run with `exec` its own cache directory
+    pub per_version_cache: bool,
+
+    /// Only use cached release lists and downloads
+    pub offline: bool,
+
+    /// Seconds a cached release list is used before it is revalidated
+    pub cache_ttl: Option<u64>,
+}
+
+/// Where releases are listed from unless a release source is configured
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Where release assets are downloaded from unless an asset mirror is configured
+const GITHUB_URL: &str = "https://github.com";
+
+const DEFAULT_HTTP_RETRIES: u32 = 3;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a cached release index is used without asking GitHub whether it changed
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Returns the wasmenv configuration, reading it on first use.
+/// A missing config file results in the default configuration.
+pub fn config() -> anyhow::Result<&'static Config> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let path = wasmenv_config_dir()?.join("config.toml");
+    let config = match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).context(format!("Parse {:?}", path))?,
+        Err(_) => Config::default(),
+    };
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// Token for the GitHub API, from `WASMENV_GITHUB_TOKEN`, `GITHUB_TOKEN` or the config file
+pub fn github_token() -> anyhow::Result<Option<String>> {
+    for var in ["WASMENV_GITHUB_TOKEN", "GITHUB_TOKEN"] {
+        if let Ok(token) = env::var(var) {
+            if !token.is_empty() {
+                return Ok(Some(token));
+            }
+        }
+    }
+    Ok(config()?.github_token.clone())
+}
+
+/// Whether to wait for the GitHub API rate limit to reset, from `WASMENV_WAIT_ON_RATE_LIMIT`
+/// or the config file
+pub fn wait_on_rate_limit() -> anyhow::Result<bool> {
+    flag("WASMENV_WAIT_ON_RATE_LIMIT", |config| {
+        config.wait_on_rate_limit
+    })
+}
+
+/// Reads an on/off setting from an environment variable, falling back to the config file
+fn flag(var: &str, from_config: impl FnOnce(&Config) -> bool) -> anyhow::Result<bool> {
+    match env::var(var) {
+        Ok(val) => Ok(!matches!(val.as_str(), "" | "0" | "false")),
+        Err(_) => Ok(from_config(config()?)),
+    }
+}
+
+/// Reads a setting from an environment variable, falling back to the config file
+fn setting<T: FromStr>(
+    var: &str,
+    from_config: impl FnOnce(&Config) -> Option<T>,
+) -> anyhow::Result<Option<T>> {
+    match env::var(var) {
+        Ok(val) if !val.is_empty() => match val.parse() {
+            Ok(val) => Ok(Some(val)),
+            Err(_) => Err(anyhow::anyhow!("Invalid value `{}` for {}", val, var)),
+        },
+        _ => Ok(from_config(config()?)),
+    }
+}
+
+/// How many times a failed request is retried, from `WASMENV_HTTP_RETRIES` or the config file
+pub fn http_retries() -> anyhow::Result<u32> {
+    Ok(
+        setting("WASMENV_HTTP_RETRIES", |config| config.http_retries)?
+            .unwrap_or(DEFAULT_HTTP_RETRIES),
+    )
+}
+
+/// From `WASMENV_CONNECT_TIMEOUT` or the config file
+pub fn connect_timeout() -> anyhow::Result<Duration> {
+    Ok(
+        setting("WASMENV_CONNECT_TIMEOUT", |config| config.connect_timeout)?
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT),
+    )
+}
+
+/// From `WASMENV_READ_TIMEOUT` or the config file
+pub fn read_timeout() -> anyhow::Result<Duration> {
+    Ok(
+        setting("WASMENV_READ_TIMEOUT", |config| config.read_timeout)?
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_READ_TIMEOUT),
+    )
+}
+
+/// Proxy for all requests from `WASMENV_PROXY` or the config file. When not set, the usual
+/// `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` variables apply.
+pub fn proxy() -> anyhow::Result<Option<String>> {
+    setting("WASMENV_PROXY", |config| config.proxy.clone())
+}
+
+/// Extra certificate authorities, from `WASMENV_CA_BUNDLE` or the config file
+pub fn ca_bundle() -> anyhow::Result<Option<PathBuf>> {
+    setting("WASMENV_CA_BUNDLE", |config| config.ca_bundle.clone())
+}
+
+/// Whether to use rustls with the bundled webpki roots instead of the system TLS library and
+/// trust store, from `WASMENV_BUNDLED_CERTS` or the config file
+pub fn bundled_certs() -> anyhow::Result<bool> {
+    flag("WASMENV_BUNDLED_CERTS", |config| config.bundled_certs)
+}
+
+/// Whether wasmenv should stay away from the network, set with `--offline`,
+/// `WASMENV_OFFLINE` or the config file
+pub fn is_offline() -> anyhow::Result<bool> {
+    flag("WASMENV_OFFLINE", |config| config.offline)
+}
+
+/// TTL of the release index, from `WASMENV_CACHE_TTL` in seconds or the config file
+pub fn cache_ttl() -> anyhow::Result<Duration> {
+    Ok(setting("WASMENV_CACHE_TTL", |config| config.cache_ttl)?
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CACHE_TTL))
+}
+
+/// Whether shims install missing versions, from `WASMENV_AUTO_INSTALL` or the config file
+pub fn auto_install() -> anyhow::Result<bool> {
+    flag("WASMENV_AUTO_INSTALL", |config| config.auto_install)
+}
+
+/// Whether `exec` gives every version its own cache, from `WASMENV_PER_VERSION_CACHE` or the
+/// config file
+pub fn per_version_cache() -> anyhow::Result<bool> {
+    flag("WASMENV_PER_VERSION_CACHE", |config| {
+        config.per_version_cache
+    })
+}
+
+/// Base URL of the releases API, from `WASMENV_RELEASE_SOURCE` or the config file
+pub fn release_source() -> anyhow::Result<String> {
+    let source = match env::var("WASMENV_RELEASE_SOURCE") {
+        Ok(source) if !source.is_empty() => source,
+        _ => config()?
+            .release_source
+            .clone()
+            .unwrap_or(GITHUB_API_URL.to_string()),
+    };
+    Ok(source.trim_end_matches('/').to_string())
+}
+
+/// Rewrites a release asset URL to point at the mirror configured with `WASMENV_ASSET_MIRROR`
+/// or in the config file. URLs not on GitHub are left alone.
+pub fn asset_url(url: &str) -> anyhow::Result<String> {
+    let mirror = match env::var("WASMENV_ASSET_MIRROR") {
+        Ok(mirror) if !mirror.is_empty() => Some(mirror),
+        _ => config()?.asset_mirror.clone(),
+    };
+    Ok(match mirror {
+        Some(mirror) => rewrite_asset_url(url, &mirror),
+        None => url.to_string(),
+    })
+}
+
+fn rewrite_asset_url(url: &str, mirror: &str) -> String {
+    match url.strip_prefix(GITHUB_URL) {
+        Some(path) if path.starts_with('/') => format!("{}{}", mirror.trim_end_matches('/'), path),
+        _ => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str("github_token = \"ghp_abc\"").unwrap();
+        assert_eq!(config.github_token.as_deref(), Some("ghp_abc"));
+        assert!(!config.wait_on_rate_limit);
+
+        assert!(toml::from_str::<Config>("githubtoken = \"ghp_abc\"").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_asset_url() {
+        let url =
+            "https://github.com/wasmerio/wasmer/releases/download/v4.2.0/wasmer-linux-amd64.tar.gz";
+        assert_eq!(
+            rewrite_asset_url(url, "https://mirror.example.com/github/"),
+            "https://mirror.example.com/github/wasmerio/wasmer/releases/download/v4.2.0/wasmer-linux-amd64.tar.gz"
+        );
+        assert_eq!(
+            rewrite_asset_url(
+                "https://github.company.com/x.tar.gz",
+                "https://mirror.example.com"
+            ),
+            "https://github.company.com/x.tar.gz"
+        );
+    }
+}
@@ -1,16 +1,36 @@
-use clap::{command, Parser, Subcommand};
+use clap::{Parser, Subcommand};
 use cmd::{
-    clear_cache::clear_cache, current::current, exec::exec, install::install, list::list,
+    bisect::bisect_releases,
+    clear_cache::clear_cache,
+    current::current,
+    exec::{exec, ExecCommand},
+    hook_env::hook_env,
+    install::install,
+    list::list,
+    local::local,
+    matrix::matrix,
     shell::shell,
+    shell_use::shell_use,
+    shims::{run_shim, shim_target, shims},
+    uninstall::uninstall,
 };
-use semver_eq::VersionReq;
+use semver_eq::{Version, VersionReq};
 use std::{
     io::{self, Read},
+    path::Path,
     str::FromStr,
 };
+mod cache;
+mod checksum;
 mod cmd;
+mod config;
+mod project;
+use project::{requested_version, version_from_env};
 use std::env;
 
+mod runtime;
+use runtime::RuntimeKind;
+
 mod utils;
 use anyhow::Result;
 #[derive(Parser)]
@@ -18,37 +38,172 @@ use anyhow::Result;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Only use cached release lists and downloads (also set with WASMENV_OFFLINE=1)
+    #[arg(long, global = true, default_value = "false")]
+    offline: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Display the currently active version of wasmer
+    /// Display the currently active version of a runtime
     Current {
+        /// Runtime to show the version of
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
         #[arg(long, default_value = "false")]
         verbose: bool,
     },
 
+    /// Run a command with every release of a runtime matching a version
+    Matrix {
+        /// Runtime to run the command with
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
+        /// Releases to run the command with
+        version: VersionReq,
+
+        /// Command to run with each release
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+
+        /// Include pre-releases
+        #[arg(long, default_value = "false")]
+        prerelease: bool,
+
+        /// How many releases to run the command with at the same time
+        #[arg(long, short, default_value = "1")]
+        jobs: usize,
+
+        /// Print the results as JSON
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+
+    /// Find the first release of a runtime for which a test command fails
+    Bisect {
+        /// Runtime to bisect
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
+        /// A version for which the test command succeeds
+        #[arg(long)]
+        good: Version,
+
+        /// A version for which the test command fails
+        #[arg(long)]
+        bad: Version,
+
+        /// Test command, exiting with 0 when a version is good and 125 when it can't be tested
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+
+        /// Include pre-releases
+        #[arg(long, default_value = "false")]
+        prerelease: bool,
+    },
+
     /// Clears the cache
     ClearCache {},
 
+    /// Create shims which run the versions of the runtimes requested for the current directory
+    Shims {},
+
     /// Configure wasmenv for a specific shell (bash, zsh, fish)
     Shell {
         /// Specify a shell name, gives output for current shell if not specified
         name: Option<String>,
+
+        /// Also switch versions when entering a directory with a version file
+        #[arg(long, default_value = "false")]
+        hook: bool,
+    },
+
+    /// Print the environment switching the current shell session to a version of a runtime.
+    /// Use it as `eval "$(wasmenv shell-use 4.2)"`, or `wasmenv shell-use 4.2 | source` in fish
+    ShellUse {
+        /// Runtime to switch
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
+        /// Version to use in this shell
+        #[arg(required_unless_present = "unset")]
+        version: Option<VersionReq>,
+
+        /// Specify a shell name, gives output for current shell if not specified
+        #[arg(long)]
+        shell: Option<String>,
+
+        /// install pre-release
+        #[arg(long, default_value = "false")]
+        prerelease: bool,
+
+        /// Go back to the version picked with `wasmenv use`
+        #[arg(long, default_value = "false", conflicts_with = "version")]
+        unset: bool,
+    },
+
+    /// Print the environment for the versions requested in the current directory, used by
+    /// the hook from `wasmenv shell --hook`
+    HookEnv {
+        /// Specify a shell name, gives output for current shell if not specified
+        name: Option<String>,
     },
 
-    /// Install wasmer
+    /// Install a version of a runtime
     Use {
+        /// Runtime to install
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
         /// Use a specific version. Install the latest version if not specified
         version: Option<VersionReq>,
 
         /// install pre-release
         #[arg(long, default_value = "false")]
         prerelease: bool,
+
+        /// Look for the newest matching release even if a matching version is installed
+        #[arg(long, visible_alias = "latest", default_value = "false")]
+        remote: bool,
     },
 
-    /// List all the available versions of wasmer
+    /// Pin a version of a runtime for the current directory in `.tool-versions`
+    Local {
+        /// Runtime to pin
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
+        /// Version to pin, resolved to the installed or newest matching release
+        version: VersionReq,
+    },
+
+    /// Remove installed versions of a runtime
+    Uninstall {
+        /// Runtime to uninstall
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
+        /// Installed versions to remove
+        version: VersionReq,
+
+        /// Also remove the version currently in use
+        #[arg(long, default_value = "false")]
+        force: bool,
+
+        /// Also remove the downloaded release archives from the cache
+        #[arg(long, default_value = "false")]
+        purge: bool,
+    },
+
+    /// List all the available versions of a runtime
     List {
+        /// Runtime to list the versions of
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
         /// Filter versions based on semver
         version: Option<VersionReq>,
 
@@ -58,15 +213,25 @@ enum Commands {
 
         #[arg(long, short, default_value = "false")]
         all: bool,
+
+        /// Only list the versions installed locally
+        #[arg(long, default_value = "false")]
+        installed: bool,
     },
 
-    /// Run command with wasmer
+    /// Run command with a runtime
     Exec {
+        /// Runtime to run the command with
+        #[arg(long, short, value_enum, default_value_t)]
+        runtime: RuntimeKind,
+
         /// Filter versions based on semver
         #[arg(long, short)]
         use_version: Option<VersionReq>,
 
-        /// wasmer command to run
+        /// Command of the runtime to run. When it starts with a version, as in
+        /// `wasmenv exec 4.2 -- cargo build`, any command is run with that version instead
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         command: Vec<String>,
 
         /// install pre-release
@@ -89,55 +254,117 @@ fn get_version_from_stdin() -> Option<VersionReq> {
     Some(VersionReq::from_str(&buffer).unwrap())
 }
 
-fn get_version_from_env() -> Option<VersionReq> {
-    match env::var("WASMER_VERSION") {
-        Ok(val) => Some(VersionReq::from_str(&val).unwrap()),
-        Err(_) => None,
+fn main() -> Result<()> {
+    let mut args = env::args_os();
+    let argv0 = args.next().unwrap_or_default();
+    if let Some((runtime, binary)) = shim_target(Path::new(&argv0)) {
+        return run_shim(runtime, binary, args.collect(), version_from_env(runtime)?);
     }
-}
 
-fn main() -> Result<()> {
     let cli = Cli::parse();
+    if cli.offline {
+        env::set_var("WASMENV_OFFLINE", "1");
+    }
 
-    let stdin_version = get_version_from_stdin();
-    let user_specified_version = if stdin_version.is_some() {
-        stdin_version
-    } else {
-        get_version_from_env()
+    // stdin of `exec` belongs to the command it runs
+    let stdin_version = match cli.command {
+        Commands::Use { .. } | Commands::List { .. } | Commands::Current { .. } => {
+            get_version_from_stdin()
+        }
+        _ => None,
     };
 
     let command = cli.command;
     match command {
         Commands::Use {
+            runtime,
             version,
             prerelease,
+            remote,
         } => {
-            let version_to_use = if version.is_some() {
-                version
-            } else {
-                user_specified_version
-            };
-            install(version_to_use, prerelease)
+            let runtime = runtime.runtime();
+            let env_version = version_from_env(runtime)?;
+            match requested_version(runtime, version, stdin_version, env_version)? {
+                Some(requested) => install(
+                    runtime,
+                    Some(requested.version),
+                    prerelease || requested.prerelease,
+                    remote,
+                ),
+                None => install(runtime, None, prerelease, remote),
+            }
         }
+        Commands::Local { runtime, version } => local(runtime.runtime(), version),
+        Commands::Uninstall {
+            runtime,
+            version,
+            force,
+            purge,
+        } => uninstall(runtime.runtime(), version, force, purge),
         Commands::List {
+            runtime,
             version,
             count,
             all,
+            installed,
         } => {
-            let version_to_use = if version.is_some() {
-                version
-            } else {
-                user_specified_version
+            let runtime = runtime.runtime();
+            let version_to_use = match version.or(stdin_version) {
+                Some(version) => Some(version),
+                None => version_from_env(runtime)?,
             };
-            list(version_to_use, count, all)
+            list(runtime, version_to_use, count, all, installed)
+        }
+        Commands::Current { runtime, verbose } => {
+            let runtime = runtime.runtime();
+            let env_version = version_from_env(runtime)?;
+            let requested = requested_version(runtime, None, stdin_version, env_version)?;
+            current(runtime, requested, verbose)
         }
-        Commands::Current { verbose } => current(verbose),
-        Commands::Shell { name } => shell(name),
+        Commands::Shell { name, hook } => shell(name, hook),
+        Commands::HookEnv { name } => hook_env(name),
+        Commands::ShellUse {
+            runtime,
+            version,
+            shell,
+            prerelease,
+            unset,
+        } => shell_use(runtime.runtime(), version, shell, prerelease, unset),
         Commands::Exec {
+            runtime,
             use_version,
             command,
             prerelease,
-        } => exec(use_version, command, prerelease),
+        } => {
+            let runtime = runtime.runtime();
+            let (use_version, command) = ExecCommand::parse(use_version, command);
+            let env_version = version_from_env(runtime)?;
+            match requested_version(runtime, use_version, stdin_version, env_version)? {
+                Some(requested) => exec(
+                    runtime,
+                    Some(requested.version),
+                    command,
+                    prerelease || requested.prerelease,
+                ),
+                None => exec(runtime, None, command, prerelease),
+            }
+        }
+        Commands::Matrix {
+            runtime,
+            version,
+            command,
+            prerelease,
+            jobs,
+            json,
+        } => matrix(runtime.runtime(), version, command, prerelease, jobs, json),
+        Commands::Bisect {
+            runtime,
+            good,
+            bad,
+            command,
+            prerelease,
+        } => bisect_releases(runtime.runtime(), good, bad, command, prerelease),
         Commands::ClearCache {} => clear_cache(),
+        Commands::Shims {} => shims(),
     }
 }
@@ -0,0 +1,378 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use semver_eq::VersionReq;
+use serde::Deserialize;
+
+use crate::runtime::Runtime;
+
+/// Name of the project file which can pin any runtime, next to the `.<runtime>-version` files
+const PROJECT_CONFIG_FILE: &str = "wasmenv.toml";
+
+/// asdf and mise file listing one `<tool> <version>` line per tool
+pub const TOOL_VERSIONS_FILE: &str = ".tool-versions";
+
+/// Contents of a `wasmenv.toml` project file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectConfig {
+    /// Runtime the project uses, wasmer unless set
+    runtime: Option<String>,
+
+    /// Version requirement for the runtime
+    version: String,
+
+    /// Whether prereleases may be used to satisfy the requirement
+    #[serde(default)]
+    prerelease: bool,
+}
+
+/// Where the version to use was taken from, in order of precedence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSource {
+    /// Given as an argument on the command line
+    Argument,
+    /// Piped into wasmenv
+    Stdin,
+    /// The version environment variable of the runtime, e.g. `WASMER_VERSION`
+    Environment(String),
+    /// A version file of the project in the current directory or one of its parents
+    File(PathBuf),
+}
+
+impl fmt::Display for VersionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionSource::Argument => write!(f, "command line argument"),
+            VersionSource::Stdin => write!(f, "stdin"),
+            VersionSource::Environment(var) => write!(f, "{}", var),
+            VersionSource::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Precedence of the places a version is looked up in, as shown by `current --verbose`
+pub const VERSION_PRECEDENCE: &str = "command line argument > stdin > <RUNTIME>_VERSION > \
+    wasmenv.toml, .<runtime>-version or .tool-versions in the current directory or a parent";
+
+/// A version requirement for a runtime and where it came from
+#[derive(Debug, Clone)]
+pub struct RequestedVersion {
+    pub version: VersionReq,
+    pub source: VersionSource,
+    pub prerelease: bool,
+}
+
+/// Finds the version of `tool` in the contents of a `.tool-versions` file. asdf allows
+/// fallback versions after the first one, which are ignored.
+fn parse_tool_versions<'a>(contents: &'a str, tool: &str) -> Option<&'a str> {
+    contents.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+        (parts.next()? == tool).then(|| parts.next()).flatten()
+    })
+}
+
+/// Sets the version of `tool` in the contents of a `.tool-versions` file, replacing the
+/// line of the tool if there is one and keeping everything else as it is
+pub fn set_tool_version(contents: &str, tool: &str, version: &str) -> String {
+    let mut found = false;
+    let mut lines: Vec<String> = contents
+        .lines()
+        .map(|line| {
+            let is_tool = line.split_whitespace().next() == Some(tool);
+            if is_tool && !found {
+                found = true;
+                format!("{tool} {version}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(format!("{tool} {version}"));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// What a `.tool-versions` file says about a runtime
+#[derive(Debug)]
+enum ToolVersion {
+    /// The file has no line for the runtime
+    NotListed,
+    /// The file pins a version wasmenv can use
+    Pinned(RequestedVersion),
+    /// The file has a line for the runtime with a version asdf understands but wasmenv
+    /// doesn't, such as `system`, `latest` or `ref:<commit>`
+    Unpinned,
+}
+
+fn read_tool_versions(runtime: &dyn Runtime, path: &Path) -> anyhow::Result<ToolVersion> {
+    let contents = fs::read_to_string(path).context(format!("Read {:?}", path))?;
+    let Some(version) = parse_tool_versions(&contents, runtime.name()) else {
+        return Ok(ToolVersion::NotListed);
+    };
+    let Ok(version) = VersionReq::from_str(version) else {
+        return Ok(ToolVersion::Unpinned);
+    };
+    Ok(ToolVersion::Pinned(RequestedVersion {
+        version,
+        source: VersionSource::File(path.to_path_buf()),
+        prerelease: false,
+    }))
+}
+
+/// Name of the plain version file of a runtime, e.g. `.wasmer-version`
+fn version_file_name(runtime: &dyn Runtime) -> String {
+    format!(".{}-version", runtime.name())
+}
+
+fn read_version_file(path: &Path) -> anyhow::Result<VersionReq> {
+    let contents = fs::read_to_string(path).context(format!("Read {:?}", path))?;
+    VersionReq::from_str(contents.trim()).context(format!("Parse the version in {:?}", path))
+}
+
+/// Reads the pin for `runtime` from a `wasmenv.toml`, which may be for another runtime
+fn read_project_config(
+    runtime: &dyn Runtime,
+    path: &Path,
+) -> anyhow::Result<Option<RequestedVersion>> {
+    let contents = fs::read_to_string(path).context(format!("Read {:?}", path))?;
+    let config: ProjectConfig = toml::from_str(&contents).context(format!("Parse {:?}", path))?;
+    if config.runtime.as_deref().unwrap_or("wasmer") != runtime.name() {
+        return Ok(None);
+    }
+    let version = VersionReq::from_str(config.version.trim())
+        .context(format!("Parse the version in {:?}", path))?;
+    Ok(Some(RequestedVersion {
+        version,
+        source: VersionSource::File(path.to_path_buf()),
+        prerelease: config.prerelease,
+    }))
+}
+
+/// Walks up from `dir` to the nearest directory pinning a version of `runtime`. Within a
+/// directory, `wasmenv.toml` takes precedence over `.<runtime>-version`, which takes
+/// precedence over `.tool-versions`. As in asdf, a `.tool-versions` line for the runtime ends
+/// the walk even when wasmenv can't use its version.
+fn find_version_file_from(
+    runtime: &dyn Runtime,
+    dir: &Path,
+) -> anyhow::Result<Option<RequestedVersion>> {
+    for dir in dir.ancestors() {
+        let project_config = dir.join(PROJECT_CONFIG_FILE);
+        if project_config.is_file() {
+            if let Some(requested) = read_project_config(runtime, &project_config)? {
+                return Ok(Some(requested));
+            }
+        }
+        let version_file = dir.join(version_file_name(runtime));
+        if version_file.is_file() {
+            return Ok(Some(RequestedVersion {
+                version: read_version_file(&version_file)?,
+                source: VersionSource::File(version_file),
+                prerelease: false,
+            }));
+        }
+        let tool_versions = dir.join(TOOL_VERSIONS_FILE);
+        if tool_versions.is_file() {
+            match read_tool_versions(runtime, &tool_versions)? {
+                ToolVersion::Pinned(requested) => return Ok(Some(requested)),
+                ToolVersion::Unpinned => return Ok(None),
+                ToolVersion::NotListed => {}
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the version of `runtime` pinned by the project in the current directory
+pub fn find_version_file(runtime: &dyn Runtime) -> anyhow::Result<Option<RequestedVersion>> {
+    find_version_file_from(runtime, &env::current_dir()?)
+}
+
+/// Reads the version requested in the environment variable of `runtime`, so that
+/// `WASMER_VERSION` doesn't pin wasmtime
+pub fn version_from_env(runtime: &dyn Runtime) -> anyhow::Result<Option<VersionReq>> {
+    let var = runtime.version_var();
+    match env::var(&var) {
+        Ok(val) => VersionReq::from_str(val.trim())
+            .map(Some)
+            .context(format!("Invalid version `{}` in {}", val, var)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(err).context(format!("Read {}", var)),
+    }
+}
+
+/// Picks the requested version of `runtime` from the first place that has one, following
+/// `VERSION_PRECEDENCE`
+pub fn requested_version(
+    runtime: &dyn Runtime,
+    argument: Option<VersionReq>,
+    stdin: Option<VersionReq>,
+    environment: Option<VersionReq>,
+) -> anyhow::Result<Option<RequestedVersion>> {
+    let given = [
+        (argument, VersionSource::Argument),
+        (stdin, VersionSource::Stdin),
+        (
+            environment,
+            VersionSource::Environment(runtime.version_var()),
+        ),
+    ]
+    .into_iter()
+    .find_map(|(version, source)| {
+        Some(RequestedVersion {
+            version: version?,
+            source,
+            prerelease: false,
+        })
+    });
+    match given {
+        Some(requested) => Ok(Some(requested)),
+        None => find_version_file(runtime),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{Wasmer, Wasmtime};
+
+    #[test]
+    fn test_find_version_file() -> anyhow::Result<()> {
+        let project = tempfile::tempdir()?;
+        let nested = project.path().join("crates/app");
+        fs::create_dir_all(&nested)?;
+        assert!(find_version_file_from(&Wasmer, &nested)?.is_none());
+
+        fs::write(project.path().join(".wasmer-version"), "4.2\n")?;
+        let requested = find_version_file_from(&Wasmer, &nested)?.unwrap();
+        assert_eq!(requested.version, VersionReq::from_str("4.2")?);
+        assert_eq!(
+            requested.source,
+            VersionSource::File(project.path().join(".wasmer-version"))
+        );
+        assert!(find_version_file_from(&Wasmtime, &nested)?.is_none());
+
+        fs::write(
+            nested.join(PROJECT_CONFIG_FILE),
+            "runtime = \"wasmtime\"\nversion = \"^14\"\nprerelease = true\n",
+        )?;
+        let requested = find_version_file_from(&Wasmtime, &nested)?.unwrap();
+        assert_eq!(requested.version, VersionReq::from_str("^14")?);
+        assert!(requested.prerelease);
+        assert_eq!(
+            find_version_file_from(&Wasmer, &nested)?.unwrap().version,
+            VersionReq::from_str("4.2")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_tool_versions() {
+        let contents = "nodejs 20.1.0\n# wasmer 1.0.0\nwasmer 4.2.0 system # pinned\n";
+        assert_eq!(parse_tool_versions(contents, "wasmer"), Some("4.2.0"));
+        assert_eq!(parse_tool_versions(contents, "wasmtime"), None);
+        assert_eq!(parse_tool_versions("wasmer\n", "wasmer"), None);
+    }
+
+    #[test]
+    fn test_read_tool_versions() -> anyhow::Result<()> {
+        let project = tempfile::tempdir()?;
+        let path = project.path().join(TOOL_VERSIONS_FILE);
+        fs::write(&path, "wasmer 4.2.0\nwasmtime system\n")?;
+        assert!(matches!(
+            read_tool_versions(&Wasmer, &path)?,
+            ToolVersion::Pinned(requested) if requested.version == VersionReq::from_str("4.2.0")?
+        ));
+        assert!(matches!(
+            read_tool_versions(&Wasmtime, &path)?,
+            ToolVersion::Unpinned
+        ));
+
+        for version in ["latest", "ref:v4.2.0", "path:/opt/wasmer"] {
+            fs::write(&path, format!("wasmer {version}\n"))?;
+            assert!(matches!(
+                read_tool_versions(&Wasmer, &path)?,
+                ToolVersion::Unpinned
+            ));
+        }
+        fs::write(&path, "nodejs 20.1.0\n")?;
+        assert!(matches!(
+            read_tool_versions(&Wasmer, &path)?,
+            ToolVersion::NotListed
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpinned_tool_version_hides_parent_pin() -> anyhow::Result<()> {
+        let parent = tempfile::tempdir()?;
+        let project = parent.path().join("proj");
+        fs::create_dir_all(&project)?;
+        fs::write(parent.path().join(".wasmer-version"), "4.2\n")?;
+        fs::write(project.join(TOOL_VERSIONS_FILE), "nodejs 20.1.0\n")?;
+        assert!(find_version_file_from(&Wasmer, &project)?.is_some());
+
+        fs::write(project.join(TOOL_VERSIONS_FILE), "wasmer system\n")?;
+        assert!(find_version_file_from(&Wasmer, &project)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_tool_version() {
+        assert_eq!(set_tool_version("", "wasmer", "4.2.0"), "wasmer 4.2.0\n");
+        assert_eq!(
+            set_tool_version("nodejs 20.1.0\nwasmer 3.0.0\n", "wasmer", "4.2.0"),
+            "nodejs 20.1.0\nwasmer 4.2.0\n"
+        );
+        assert_eq!(
+            set_tool_version("nodejs 20.1.0", "wasmer", "4.2.0"),
+            "nodejs 20.1.0\nwasmer 4.2.0\n"
+        );
+    }
+
+    #[test]
+    fn test_requested_version_precedence() -> anyhow::Result<()> {
+        let requested = requested_version(
+            &Wasmer,
+            None,
+            Some(VersionReq::from_str("3")?),
+            Some(VersionReq::from_str("4")?),
+        )?
+        .unwrap();
+        assert_eq!(requested.source, VersionSource::Stdin);
+        assert_eq!(requested.version, VersionReq::from_str("3")?);
+
+        let requested =
+            requested_version(&Wasmtime, None, None, Some(VersionReq::from_str("14")?))?.unwrap();
+        assert_eq!(
+            requested.source,
+            VersionSource::Environment("WASMTIME_VERSION".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_from_env() -> anyhow::Result<()> {
+        env::set_var("WASMER_VERSION", "4.2");
+        env::remove_var("WASMTIME_VERSION");
+        assert_eq!(
+            version_from_env(&Wasmer)?,
+            Some(VersionReq::from_str("4.2")?)
+        );
+        assert_eq!(version_from_env(&Wasmtime)?, None);
+
+        env::set_var("WASMTIME_VERSION", "bogus");
+        let err = version_from_env(&Wasmtime).unwrap_err();
+        assert!(err.to_string().contains("WASMTIME_VERSION"));
+        env::remove_var("WASMER_VERSION");
+        env::remove_var("WASMTIME_VERSION");
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use directories::BaseDirs;
+use flate2::read::GzDecoder;
+use semver_eq::Version;
+use tar::Archive;
+
+use crate::utils::wasmenv_data_dir;
+
+pub mod wasmer;
+pub mod wasmtime;
+
+pub use self::wasmer::Wasmer;
+pub use self::wasmtime::Wasmtime;
+
+/// A wasm runtime which wasmenv can install and switch between versions of.
+pub trait Runtime: Sync {
+    /// Name of the runtime, as used on the command line and in messages
+    fn name(&self) -> &'static str;
+
+    /// GitHub repository (`owner/name`) the runtime publishes its releases to
+    fn repository(&self) -> &'static str;
+
+    /// Name of the release asset built for the given platform, if there is one
+    fn asset_name(&self, target_os: &str, target_arch: &str, version: &Version) -> Option<String>;
+
+    /// Executables shipped with the runtime. The first one is the main binary.
+    fn binaries(&self) -> &'static [&'static str];
+
+    /// Parses the output of `<binary> --version`
+    fn version_from_version_string(&self, version_string: &str) -> anyhow::Result<Version>;
+
+    /// Main executable of the runtime
+    fn binary(&self) -> &'static str {
+        self.binaries()[0]
+    }
+
+    /// Directory holding one sub-directory per installed version
+    fn versions_dir(&self) -> anyhow::Result<PathBuf> {
+        Ok(wasmenv_data_dir()?.join(self.name()))
+    }
+
+    /// Directory containing the executables of a version installed at `install_dir`
+    fn bin_dir(&self, install_dir: &Path) -> PathBuf {
+        install_dir.join("bin")
+    }
+
+    /// Environment variable the runtime reads its installation directory from
+    fn dir_var(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Environment variable the runtime reads its cache directory from
+    fn cache_dir_var(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Environment variable requesting a version of the runtime, e.g. `WASMER_VERSION`
+    fn version_var(&self) -> String {
+        format!("{}_VERSION", self.name().to_uppercase())
+    }
+
+    /// Directory the runtime's own installer puts it in, e.g. `~/.wasmer`
+    fn system_dir(&self) -> Option<PathBuf> {
+        BaseDirs::new().map(|base_dirs| base_dirs.home_dir().join(format!(".{}", self.name())))
+    }
+
+    /// Directory containing the executables the runtime's own installer put in `system_dir`
+    fn system_bin_dir(&self) -> Option<PathBuf> {
+        Some(self.bin_dir(&self.system_dir()?))
+    }
+
+    /// Unpacks a downloaded release archive into `dest_dir`
+    fn unpack(&self, archive: &Path, dest_dir: &Path) -> anyhow::Result<()> {
+        let file = File::open(archive).context(format!("Open {:?}", archive))?;
+        let mut archive = Archive::new(GzDecoder::new(file));
+        archive.unpack(dest_dir)?;
+        Ok(())
+    }
+}
+
+/// Runtimes that can be selected on the command line
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RuntimeKind {
+    #[default]
+    Wasmer,
+    Wasmtime,
+}
+
+impl RuntimeKind {
+    pub fn runtime(self) -> &'static dyn Runtime {
+        match self {
+            RuntimeKind::Wasmer => &Wasmer,
+            RuntimeKind::Wasmtime => &Wasmtime,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use semver_eq::Version;
+
+use crate::utils::wasmenv_data_dir;
+
+use super::Runtime;
+
+pub struct Wasmer;
+
+impl Runtime for Wasmer {
+    fn name(&self) -> &'static str {
+        "wasmer"
+    }
+
+    fn repository(&self) -> &'static str {
+        "wasmerio/wasmer"
+    }
+
+    fn asset_name(&self, target_os: &str, target_arch: &str, _version: &Version) -> Option<String> {
+        let filename = match (target_os, target_arch) {
+            ("linux", "x86_64") => "wasmer-linux-amd64.tar.gz",
+            ("linux", "aarch64") => "wasmer-linux-aarch64.tar.gz",
+            ("linux", "mips64") => "wasmer-linux-mips64.tar.gz",
+            ("linux", "riscv64") => "wasmer-linux-riscv64.tar.gz",
+            ("macos", "x86_64") => "wasmer-darwin-amd64.tar.gz",
+            ("macos", "aarch64") => "wasmer-darwin-arm64.tar.gz",
+            ("windows", "x86_64") => "wasmer-windows-amd64.tar.gz",
+            ("windows", "gnu") => "wasmer-windows-gnu64.tar.gz",
+            ("windows", _) => "wasmer-windows.exe",
+            _ => return None,
+        };
+        Some(filename.to_string())
+    }
+
+    fn binaries(&self) -> &'static [&'static str] {
+        &["wasmer"]
+    }
+
+    fn version_from_version_string(&self, version_string: &str) -> anyhow::Result<Version> {
+        match version_string
+            .trim()
+            .trim_start_matches("wasmer ")
+            .parse::<Version>()
+        {
+            Ok(version) => Ok(version),
+            Err(_) => Err(anyhow::anyhow!(
+                "Could not get wasmer version form the version string"
+            )),
+        }
+    }
+
+    fn dir_var(&self) -> Option<&'static str> {
+        Some("WASMER_DIR")
+    }
+
+    fn cache_dir_var(&self) -> Option<&'static str> {
+        Some("WASMER_CACHE_DIR")
+    }
+
+    /// wasmer versions live directly in the data directory, which is where
+    /// wasmenv installed them before it supported other runtimes
+    fn versions_dir(&self) -> anyhow::Result<PathBuf> {
+        wasmenv_data_dir()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_from_version_string() {
+        // Test the case where the version string is valid
+        let version = Wasmer
+            .version_from_version_string("wasmer 1.0.0\n")
+            .unwrap();
+        assert_eq!(version.to_string(), "1.0.0");
+
+        // Test the case where the version string is invalid
+        let result = Wasmer.version_from_version_string("invalid version string");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Could not get wasmer version form the version string"
+        );
+    }
+
+    #[test]
+    fn test_asset_name() {
+        let version = Version::new(3, 3, 0);
+        assert_eq!(
+            Wasmer.asset_name("linux", "x86_64", &version).as_deref(),
+            Some("wasmer-linux-amd64.tar.gz")
+        );
+        assert_eq!(Wasmer.asset_name("freebsd", "x86_64", &version), None);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    fs::{self, File},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use semver_eq::Version;
+use tar::Archive;
+use xz2::read::XzDecoder;
+
+use super::Runtime;
+
+pub struct Wasmtime;
+
+impl Runtime for Wasmtime {
+    fn name(&self) -> &'static str {
+        "wasmtime"
+    }
+
+    fn repository(&self) -> &'static str {
+        "bytecodealliance/wasmtime"
+    }
+
+    fn asset_name(&self, target_os: &str, target_arch: &str, version: &Version) -> Option<String> {
+        let platform = match (target_os, target_arch) {
+            ("linux", "x86_64") => "x86_64-linux",
+            ("linux", "aarch64") => "aarch64-linux",
+            ("linux", "s390x") => "s390x-linux",
+            ("linux", "riscv64") => "riscv64gc-linux",
+            ("macos", "x86_64") => "x86_64-macos",
+            ("macos", "aarch64") => "aarch64-macos",
+            // windows builds are only published as zip archives
+            _ => return None,
+        };
+        Some(format!("wasmtime-v{version}-{platform}.tar.xz"))
+    }
+
+    fn binaries(&self) -> &'static [&'static str] {
+        &["wasmtime", "wasmtime-min"]
+    }
+
+    /// Parses both `wasmtime-cli 9.0.1` and `wasmtime 20.0.0 (9e1084ffa 2024-04-22)`
+    fn version_from_version_string(&self, version_string: &str) -> anyhow::Result<Version> {
+        version_string
+            .trim()
+            .trim_start_matches("wasmtime-cli ")
+            .trim_start_matches("wasmtime ")
+            .split_whitespace()
+            .next()
+            .and_then(|version| version.parse::<Version>().ok())
+            .ok_or(anyhow::anyhow!(
+                "Could not get wasmtime version form the version string"
+            ))
+    }
+
+    /// Release archives contain a single `wasmtime-vX.Y.Z-<platform>` directory with the
+    /// executables at its root
+    fn bin_dir(&self, install_dir: &Path) -> PathBuf {
+        install_dir.to_path_buf()
+    }
+
+    /// Unlike release archives, the wasmtime installer puts the executables in `~/.wasmtime/bin`
+    fn system_bin_dir(&self) -> Option<PathBuf> {
+        Some(self.system_dir()?.join("bin"))
+    }
+
+    fn unpack(&self, archive: &Path, dest_dir: &Path) -> anyhow::Result<()> {
+        let file = File::open(archive).context(format!("Open {:?}", archive))?;
+        let mut archive = Archive::new(XzDecoder::new(file));
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            let Some(relative_path) = strip_top_level_dir(&path) else {
+                continue;
+            };
+            let dest_path = dest_dir.join(relative_path);
+            if let Some(parent) = dest_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            entry
+                .unpack(&dest_path)
+                .context(format!("Unpack {:?}", dest_path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Drops the first component of an archive path, refusing anything that could escape the
+/// destination directory. Returns `None` for the top-level directory itself.
+fn strip_top_level_dir(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+    components.next()?;
+    let relative_path: PathBuf = components.as_path().to_path_buf();
+    if relative_path.as_os_str().is_empty()
+        || !relative_path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(relative_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_from_version_string() {
+        let version = Wasmtime
+            .version_from_version_string("wasmtime-cli 9.0.1\n")
+            .unwrap();
+        assert_eq!(version.to_string(), "9.0.1");
+
+        let version = Wasmtime
+            .version_from_version_string("wasmtime 20.0.0 (9e1084ffa 2024-04-22)\n")
+            .unwrap();
+        assert_eq!(version.to_string(), "20.0.0");
+
+        assert!(Wasmtime
+            .version_from_version_string("wasmer 4.2.0")
+            .is_err());
+    }
+
+    #[test]
+    fn test_system_bin_dir() {
+        let system_bin_dir = Wasmtime.system_bin_dir().unwrap();
+        assert!(system_bin_dir.ends_with(".wasmtime/bin"));
+    }
+
+    #[test]
+    fn test_asset_name() {
+        let version = Version::new(14, 0, 4);
+        assert_eq!(
+            Wasmtime.asset_name("linux", "x86_64", &version).as_deref(),
+            Some("wasmtime-v14.0.4-x86_64-linux.tar.xz")
+        );
+        assert_eq!(Wasmtime.asset_name("windows", "x86_64", &version), None);
+    }
+
+    #[test]
+    fn test_strip_top_level_dir() {
+        assert_eq!(
+            strip_top_level_dir(Path::new("wasmtime-v14.0.4-x86_64-linux/wasmtime")),
+            Some(PathBuf::from("wasmtime"))
+        );
+        assert_eq!(
+            strip_top_level_dir(Path::new("wasmtime-v14.0.4-x86_64-linux/")),
+            None
+        );
+        assert_eq!(strip_top_level_dir(Path::new("top/../../etc/passwd")), None);
+    }
+}
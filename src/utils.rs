@@ -1,14 +1,12 @@
-use chrono::DateTime;
-use directories::BaseDirs;
+use chrono::{DateTime, Local};
 use dirs::{cache_dir, config_dir, data_dir};
 
 use anyhow::Context;
-use flate2::read::GzDecoder;
 use is_executable::IsExecutable;
 use semver_eq::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
-use std::fs::{create_dir_all, File};
-use std::io::{copy, Write};
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{self, copy, Write};
 use which::which;
 
 use std::{env, fs};
@@ -18,16 +16,36 @@ use std::{
     env::consts::{ARCH, OS},
     process::Command,
 };
-use tar::Archive;
 
-use std::time::Duration;
+use std::sync::OnceLock;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{
+    HeaderMap, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_NONE_MATCH, IF_RANGE,
+    LAST_MODIFIED, LINK, RANGE,
+};
+use reqwest::{Certificate, NoProxy, Proxy, StatusCode, Url};
+
+use crate::cache::cached_releases;
+use crate::checksum::{
+    record_digest, remove_download, verify_cached, verify_download, ChecksumMismatch,
+};
+use crate::config::{
+    asset_url, bundled_certs, ca_bundle, connect_timeout, github_token, http_retries, is_offline,
+    proxy, read_timeout, release_source, wait_on_rate_limit, GITHUB_API_URL,
+};
+use crate::runtime::Runtime;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ReleaseAsset {
     pub name: String,
     pub browser_download_url: String,
+    /// Digest of the asset as reported by GitHub, e.g. `sha256:<hex>`
+    #[serde(default)]
+    pub digest: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -44,20 +62,20 @@ impl Release {
         Version::parse(tag_name).unwrap()
     }
 
-    pub fn download_url(&self) -> Option<&str> {
-        let filename = get_filename_for_system_architecture(OS, ARCH);
-        self.assets
-            .iter()
-            .find(|asset| asset.name == filename)
+    /// The asset of the runtime built for this system
+    pub fn asset(&self, runtime: &dyn Runtime) -> Option<&ReleaseAsset> {
+        let filename = runtime.asset_name(OS, ARCH, &self.version())?;
+        self.assets.iter().find(|asset| asset.name == filename)
+    }
+
+    pub fn download_url(&self, runtime: &dyn Runtime) -> Option<&str> {
+        self.asset(runtime)
             .map(|asset| &asset.browser_download_url)
             .map(|x| x.as_str())
     }
 
-    pub fn filename(&self) -> Option<String> {
-        let filename = get_filename_for_system_architecture(OS, ARCH);
-        self.assets
-            .iter()
-            .find(|asset| asset.name == filename)
+    pub fn filename(&self, runtime: &dyn Runtime) -> Option<String> {
+        self.asset(runtime)
             .map(|asset| format!("{}-{}", self.version(), asset.name))
     }
 
@@ -66,126 +84,316 @@ impl Release {
         date_time.format("%B %e %Y %r").to_string()
     }
 
-    pub fn tags(&self) -> Vec<&str> {
+    pub fn tags(&self, runtime: &dyn Runtime) -> Vec<&str> {
         let mut tags: Vec<&str> = Vec::new();
         if self.prerelease {
             tags.push("prerelease");
         }
-        if let Some(system_wasmer_version) = find_system_wasmer() {
-            if system_wasmer_version == self.version() {
+        if let Some(system_version) = find_system_version(runtime) {
+            if system_version == self.version() {
                 tags.push("system")
             }
         }
+        if is_installed(runtime, &self.version()) {
+            tags.push("installed");
+        }
+        if find_linked_version(runtime) == Some(self.version()) {
+            tags.push("current");
+        }
         tags
     }
 }
 
-/// Fetches the list of releases from the Wasmer GitHub repository and returns them as a vector
-/// of `Release` objects.
+/// Releases returned by `fetch_releases`
+pub struct FetchedReleases {
+    pub releases: Vec<Release>,
+    /// `ETag` of the first page, used to revalidate the releases later on
+    pub etag: Option<String>,
+}
+
+/// Fetches the list of releases of a runtime from its GitHub repository, newest version first.
+/// Every page of the releases API is requested, and releases whose tag is not a semver version
+/// are skipped.
+///
+/// When `etag` is given it is sent as `If-None-Match`, and `None` is returned if GitHub reports
+/// that the releases have not been modified since.
 ///
 /// # Examples
 ///
 /// ```
-/// use wasmenv::Release;
+/// use wasmenv::{runtime::Wasmer, Release};
 ///
-/// let releases = wasmenv::list_releases().unwrap();
-/// for release in releases {
+/// let fetched = wasmenv::fetch_releases(&Wasmer, None).unwrap().unwrap();
+/// for release in fetched.releases {
 ///     println!("{} ({})", release.tag_name, release.published_time());
 /// }
 /// ```
-pub fn list_releases() -> Result<Vec<Release>, reqwest::Error> {
-    let url = "https://api.github.com/repos/wasmerio/wasmer/releases";
-    let client = reqwest::blocking::Client::new();
-    let response = client.get(url).header("User-Agent", "wasmenv").send()?;
-    response.json()
+pub fn fetch_releases(
+    runtime: &dyn Runtime,
+    etag: Option<&str>,
+) -> anyhow::Result<Option<FetchedReleases>> {
+    let url = format!(
+        "{}/repos/{}/releases?per_page=100",
+        release_source()?,
+        runtime.repository()
+    );
+    fetch_releases_from(&url, etag)
 }
 
-pub fn list_releases_interactively() -> Result<Vec<Release>, reqwest::Error> {
-    let progress_bar = create_progress_bar(String::from("Fetching wasmer releases..."));
-    let releases = list_releases().expect("A list of wasmer releases from github.");
-    progress_bar.finish_and_clear();
-    Ok(releases)
-}
+fn fetch_releases_from(url: &str, etag: Option<&str>) -> anyhow::Result<Option<FetchedReleases>> {
+    let mut releases: Vec<Release> = Vec::new();
+    let mut first_page_etag = None;
+    let mut first_page = true;
+    let mut next_url = Some(url.to_string());
 
-pub fn get_filename_for_system_architecture(target_os: &str, target_arch: &str) -> String {
-    let filename = match (target_os, target_arch) {
-        ("linux", "x86_64") => "wasmer-linux-amd64.tar.gz",
-        ("linux", "aarch64") => "wasmer-linux-aarch64.tar.gz",
-        ("linux", "mips64") => "wasmer-linux-mips64.tar.gz",
-        ("linux", "riscv64") => "wasmer-linux-riscv64.tar.gz",
-        ("macos", "x86_64") => "wasmer-darwin-amd64.tar.gz",
-        ("macos", "aarch64") => "wasmer-darwin-arm64.tar.gz",
-        ("windows", "x86_64") => "wasmer-windows-amd64.tar.gz",
-        ("windows", "gnu") => "wasmer-windows-gnu64.tar.gz",
-        ("windows", _) => "wasmer-windows.exe",
-        _ => panic!("Unsupported architecture: {}-{}", target_os, target_arch),
-    };
-    filename.to_string()
+    while let Some(url) = next_url {
+        let mut headers = HeaderMap::new();
+        if let (Some(etag), true) = (etag, first_page) {
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_str(etag)?);
+        }
+        let page = with_retries("Fetching releases", || {
+            let response = github_get(&url, headers.clone())?;
+            if response.status() == StatusCode::NOT_MODIFIED {
+                return Ok(None);
+            }
+            let response = response.error_for_status()?;
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value: &HeaderValue| value.to_str().ok())
+                    .map(String::from)
+            };
+            let etag = header(ETAG);
+            let next_url = header(LINK).as_deref().and_then(next_page_url);
+            let page: Vec<Release> = response.json()?;
+            Ok(Some((page, etag, next_url)))
+        })?;
+        let Some((page, etag, url)) = page else {
+            return Ok(None);
+        };
+        if first_page {
+            first_page = false;
+            first_page_etag = etag;
+        }
+        next_url = url;
+        releases.extend(page);
+    }
+
+    releases.retain(|rel| Version::parse(rel.tag_name.trim_start_matches('v')).is_ok());
+    releases.sort_by_key(|rel| std::cmp::Reverse(rel.version()));
+    releases.dedup_by(|a, b| a.tag_name == b.tag_name);
+    Ok(Some(FetchedReleases {
+        releases,
+        etag: first_page_etag,
+    }))
 }
 
-fn version_from_version_string(version_string: String) -> anyhow::Result<Version> {
-    match version_string
-        .trim()
-        .trim_start_matches("wasmer ")
-        .parse::<Version>()
-    {
-        Ok(version) => Ok(version),
-        Err(_) => Err(anyhow::anyhow!(
-            "Could not get wasmer version form the version string"
-        )),
-    }
+/// Whether `url` points at the GitHub API itself. The host is compared exactly, so that
+/// e.g. `https://api.github.com.example.net` doesn't count.
+fn is_github_api_url(url: &str) -> bool {
+    let (Ok(url), Ok(github)) = (Url::parse(url), Url::parse(GITHUB_API_URL)) else {
+        return false;
+    };
+    url.scheme() == github.scheme()
+        && url.host_str() == github.host_str()
+        && url.port_or_known_default() == github.port_or_known_default()
 }
 
-/// Searches for the system Wasmer binary and returns its version.
+/// Sends a GET request to the releases API, authenticated when it is GitHub's.
 ///
-/// Returns `None` if Wasmer is not installed or the installed version is not compatible.
-pub fn find_system_wasmer() -> Option<Version> {
-    // Try to locate the Wasmer binary in the user's home directory.
-    let wasmer_path = BaseDirs::new().map(|base_dirs| {
-        let wasmer_path = base_dirs.home_dir().join(".wasmer/bin/wasmer");
-        if wasmer_path.is_executable() {
-            Some(wasmer_path)
-        } else {
-            None
+/// When the rate limit is exceeded, either waits for it to reset and tries again if
+/// `wait_on_rate_limit` is set, or fails with an error telling when it resets.
+fn github_get(url: &str, headers: HeaderMap) -> anyhow::Result<Response> {
+    // never hand the GitHub token to a mirror
+    let token = if is_github_api_url(url) {
+        github_token()?
+    } else {
+        None
+    };
+    loop {
+        let mut request = http_client()?
+            .get(url)
+            .header(ACCEPT, "application/vnd.github+json")
+            .headers(headers.clone());
+        if let Some(token) = &token {
+            request = request.bearer_auth(token);
         }
-    })?;
-
-    if let Some(wasmer_path) = wasmer_path {
-        let output = Command::new(wasmer_path).arg("--version").output().ok()?;
-        if output.status.success() {
-            let version_str = String::from_utf8_lossy(&output.stdout).to_string();
-            if let Ok(version) = version_from_version_string(version_str) {
-                return Some(version);
+        let response = request.send()?;
+
+        let Some(reset) = rate_limit_reset(&response) else {
+            return Ok(response);
+        };
+        let reset_time = DateTime::<Local>::from(reset).format("%r");
+        if !wait_on_rate_limit()? {
+            let hint = if token.is_none() {
+                "Set GITHUB_TOKEN or WASMENV_GITHUB_TOKEN to raise the limit, \
+                or WASMENV_WAIT_ON_RATE_LIMIT=1 to wait for the reset."
             } else {
-                return None;
-            }
+                "Set WASMENV_WAIT_ON_RATE_LIMIT=1 to wait for the reset."
+            };
+            return Err(anyhow::anyhow!(
+                "GitHub API rate limit exceeded, it resets at {}.\n{}",
+                reset_time,
+                hint
+            ));
         }
+        eprintln!(
+            "GitHub API rate limit exceeded, waiting until {} to try again...",
+            reset_time
+        );
+        sleep(
+            reset
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::from_secs(1)),
+        );
     }
-    None
 }
 
-/// Searches for the system Wasmer binary and returns its version.
-///
-/// Returns `None` if Wasmer is not installed or the installed version is not compatible.
-pub fn find_current_wasmer() -> Option<Version> {
-    let output = Command::new("wasmer").arg("--version").output().ok()?;
+/// Returns when the rate limit resets if the response was refused because of it
+fn rate_limit_reset(response: &Response) -> Option<SystemTime> {
+    if !matches!(
+        response.status(),
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) {
+        return None;
+    }
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+    // secondary rate limits tell how many seconds to wait instead
+    if let Some(retry_after) = header("retry-after") {
+        return Some(SystemTime::now() + Duration::from_secs(retry_after));
+    }
+    if header("x-ratelimit-remaining") != Some(0) {
+        return None;
+    }
+    header("x-ratelimit-reset").map(|reset| UNIX_EPOCH + Duration::from_secs(reset))
+}
+
+/// Extracts the `rel="next"` URL from a GitHub `Link` header, e.g.
+/// `<https://api.github.com/...?page=2>; rel="next", <https://api.github.com/...?page=5>; rel="last"`
+fn next_page_url(link_header: &str) -> Option<String> {
+    link_header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| param.trim().replace(' ', "") == "rel=\"next\"");
+        if !is_next {
+            return None;
+        }
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        Some(url.to_string())
+    })
+}
+
+pub fn list_releases_interactively(runtime: &dyn Runtime) -> anyhow::Result<Vec<Release>> {
+    let progress_bar = create_progress_bar(format!("Fetching {} releases...", runtime.name()));
+    let releases = cached_releases(runtime);
+    progress_bar.finish_and_clear();
+    releases
+}
+
+fn version_of_executable(runtime: &dyn Runtime, executable: &Path) -> Option<Version> {
+    let output = Command::new(executable).arg("--version").output().ok()?;
     if output.status.success() {
         let version_str = String::from_utf8_lossy(&output.stdout).to_string();
-        if let Ok(version) = version_from_version_string(version_str) {
+        if let Ok(version) = runtime.version_from_version_string(&version_str) {
             return Some(version);
         }
     }
     None
 }
 
-/// Finds the location of current wasmer executable
-pub fn find_current_wasmer_dir() -> anyhow::Result<PathBuf> {
-    Ok(which("wasmer")?
+/// Searches for the runtime installed by its own installer (e.g. `~/.wasmer`) and returns its
+/// version.
+///
+/// Returns `None` if it is not installed or the installed version is not compatible.
+pub fn find_system_version(runtime: &dyn Runtime) -> Option<Version> {
+    let executable = runtime.system_bin_dir()?.join(runtime.binary());
+    if !executable.is_executable() {
+        return None;
+    }
+    version_of_executable(runtime, &executable)
+}
+
+/// Returns the version of the runtime found in `PATH`.
+///
+/// Returns `None` if the runtime is not in `PATH` or the version could not be determined.
+pub fn find_current_version(runtime: &dyn Runtime) -> Option<Version> {
+    version_of_executable(runtime, Path::new(runtime.binary()))
+}
+
+/// Finds the directory containing the runtime executable found in `PATH`
+pub fn find_current_dir(runtime: &dyn Runtime) -> anyhow::Result<PathBuf> {
+    Ok(which(runtime.binary())?
         .parent()
-        .expect("path to wasmer executable")
+        .expect("path to runtime executable")
         .to_path_buf())
 }
 
+/// Whether a version of the runtime is installed in its versions directory
+pub fn is_installed(runtime: &dyn Runtime, version: &Version) -> bool {
+    runtime
+        .versions_dir()
+        .map(|versions_dir| {
+            let install_dir = versions_dir.join(version.to_string());
+            runtime
+                .bin_dir(&install_dir)
+                .join(runtime.binary())
+                .exists()
+        })
+        .unwrap_or(false)
+}
+
+/// Versions of the runtime installed in its versions directory, newest first
+pub fn installed_versions(runtime: &dyn Runtime) -> anyhow::Result<Vec<Version>> {
+    let versions_dir = runtime.versions_dir()?;
+    let Ok(entries) = fs::read_dir(&versions_dir) else {
+        return Ok(Vec::new());
+    };
+    let mut versions: Vec<Version> = entries
+        .flatten()
+        .filter_map(|entry| Version::parse(entry.file_name().to_str()?).ok())
+        .filter(|version| is_installed(runtime, version))
+        .collect();
+    versions.sort_by(|a, b| b.cmp(a));
+    Ok(versions)
+}
+
+/// Finds the newest installed version of the runtime matching `version`. Prereleases are only
+/// considered when `prerelease` is set.
+pub fn find_installed_version(
+    runtime: &dyn Runtime,
+    version: &VersionReq,
+    prerelease: bool,
+) -> anyhow::Result<Option<Version>> {
+    Ok(installed_versions(runtime)?
+        .into_iter()
+        .filter(|installed| prerelease || installed.pre.is_empty())
+        .find(|installed| version.matches(installed)))
+}
+
+/// Returns the installed version the `current` symlink of the runtime points at
+pub fn find_linked_version(runtime: &dyn Runtime) -> Option<Version> {
+    let link = wasmenv_data_dir()
+        .ok()?
+        .join("current/bin")
+        .join(runtime.binary());
+    let target = fs::read_link(link).ok()?;
+    version_in_dir(&runtime.versions_dir().ok()?, &target)
+}
+
+/// Version whose directory inside `versions_dir` contains `path`
+fn version_in_dir(versions_dir: &Path, path: &Path) -> Option<Version> {
+    let version = path.strip_prefix(versions_dir).ok()?.components().next()?;
+    Version::parse(version.as_os_str().to_str()?).ok()
+}
+
 pub fn wasmenv_cache_dir() -> anyhow::Result<PathBuf> {
     Ok(cache_dir()
         .expect("Cache directory should be present")
@@ -198,52 +406,403 @@ pub fn wasmenv_data_dir() -> anyhow::Result<PathBuf> {
         .join("wasmenv"))
 }
 
-pub fn download_wasmer_to_cache(release: &Release) -> anyhow::Result<PathBuf> {
-    let url = release
-        .download_url()
-        .expect("Download url for wasmer release");
-    let filename = release.filename().expect("Filename for wasmer release");
-    let filepath = wasmenv_cache_dir()?.join(filename);
+/// Returns the HTTP client used for every request wasmenv makes, set up with the timeouts,
+/// proxy and certificate authorities from the configuration
+pub fn http_client() -> anyhow::Result<&'static Client> {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let mut builder = Client::builder()
+        .user_agent("wasmenv")
+        .connect_timeout(connect_timeout()?)
+        .timeout(read_timeout()?);
+
+    // the system proxy from HTTPS_PROXY and friends is used otherwise
+    if let Some(proxy) = proxy()? {
+        builder = builder.proxy(Proxy::all(&proxy)?.no_proxy(NoProxy::from_env()));
+    }
+
+    if bundled_certs()? {
+        builder = builder.use_rustls_tls();
+    }
+    if let Some(ca_bundle) = ca_bundle()? {
+        let contents = fs::read_to_string(&ca_bundle).context(format!("Read {:?}", ca_bundle))?;
+        let certificates = pem_certificates(&contents);
+        if certificates.is_empty() {
+            return Err(anyhow::anyhow!("No certificates found in {:?}", ca_bundle));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(
+                Certificate::from_pem(certificate.as_bytes())
+                    .context(format!("Invalid certificate in {:?}", ca_bundle))?,
+            );
+        }
+    }
+
+    Ok(CLIENT.get_or_init(|| builder.build().expect("HTTP client")))
+}
+
+/// Splits a PEM bundle into its certificates
+fn pem_certificates(bundle: &str) -> Vec<&str> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+    bundle
+        .match_indices(BEGIN)
+        .filter_map(|(start, _)| {
+            let end = bundle[start..].find(END)? + start + END.len();
+            Some(&bundle[start..end])
+        })
+        .collect()
+}
+
+/// Delay before the first retry of a failed request, doubled for every following one
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Runs a network operation, trying it again with exponential backoff when it fails with an
+/// error that might go away, like a 5xx response, a timeout or a reset connection
+pub fn with_retries<T>(
+    description: &str,
+    mut operation: impl FnMut() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let retries = http_retries()?;
+    let mut attempt = 0;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < retries && is_retryable(&err) => {
+                let delay = retry_delay(attempt);
+                eprintln!(
+                    "{} failed ({}), retrying in {:.1}s...",
+                    description,
+                    err,
+                    delay.as_secs_f64()
+                );
+                sleep(delay);
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Exponential backoff with jitter, so that clients failing together don't retry together
+fn retry_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(RETRY_MAX_DELAY);
+    delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+}
+
+fn is_retryable(err: &anyhow::Error) -> bool {
+    if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        if let Some(status) = err.status() {
+            return status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT;
+        }
+        return err.is_timeout() || err.is_connect() || err.is_body() || err.is_request();
+    }
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        // the blocking body reader wraps errors of the connection in an `io::Error`
+        if let Some(err) = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<reqwest::Error>())
+        {
+            return err.is_timeout() || err.is_connect() || err.is_body() || err.is_request();
+        }
+        return matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::UnexpectedEof
+                | io::ErrorKind::Interrupted
+        );
+    }
+    false
+}
+
+/// Path in the cache directory a release of the runtime is downloaded to
+pub fn download_path(runtime: &dyn Runtime, release: &Release) -> anyhow::Result<PathBuf> {
+    match release.filename(runtime) {
+        Some(filename) => Ok(wasmenv_cache_dir()?.join(filename)),
+        None => Err(anyhow::anyhow!(
+            "{} {} has no release for {}-{}",
+            runtime.name(),
+            release.version(),
+            OS,
+            ARCH
+        )),
+    }
+}
+
+pub fn download_to_cache(runtime: &dyn Runtime, release: &Release) -> anyhow::Result<PathBuf> {
+    let filepath = download_path(runtime, release)?;
+    let cache_dir = filepath.parent().unwrap();
+    create_dir_all(cache_dir)?;
+    // held until the download is verified and in place, so that another wasmenv downloading
+    // the same release doesn't write into the same partial download
+    let _lock = lock_download(&filepath)?;
 
     if filepath.exists() {
-        return Ok(filepath);
+        match verify_cached(runtime, release, &filepath) {
+            Ok(()) => return Ok(filepath),
+            Err(err) => {
+                eprintln!("{}, removing it from the cache.", err);
+                remove_download(&filepath)?;
+            }
+        }
     }
-    println!("downloading to {}", filepath.to_str().unwrap());
+    if is_offline()? {
+        return Err(anyhow::anyhow!(
+            "{} {} has not been downloaded yet, and wasmenv is offline",
+            runtime.name(),
+            release.version()
+        ));
+    }
+    let url = asset_url(
+        release
+            .download_url(runtime)
+            .context("Download url for release")?,
+    )?;
+    eprintln!("downloading to {}", filepath.to_str().unwrap());
 
-    create_dir_all(filepath.parent().unwrap())?;
+    remove_stale_partial_downloads(cache_dir)?;
 
-    let client = reqwest::blocking::Client::new();
-    let progress_bar = create_progress_bar(format!("Downloading wasmer {}...", release.version()));
+    // download next to the final file, so that an interrupted download is never mistaken
+    // for a complete one, and can be resumed on the next run
+    let part_path = partial_download_path(&filepath);
+    let message = format!("Downloading {} {}", runtime.name(), release.version());
+    with_retries(&message, || download_to_partial(&url, &part_path, &message))?;
 
-    let mut response = &mut client.get(url).send()?;
+    // only a corrupt download is thrown away, failing to fetch the checksum keeps it for
+    // the next run
+    let digest = match verify_download(runtime, release, &part_path) {
+        Ok(digest) => digest,
+        Err(err) if err.is::<ChecksumMismatch>() => {
+            remove_partial_download(&part_path)?;
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+    };
+    fs::rename(&part_path, &filepath)?;
+    remove_partial_download(&part_path)?;
+    record_digest(&filepath, &digest)?;
+    Ok(filepath)
+}
 
-    let mut tmp_file = File::create(&filepath)?;
+/// Downloads `url` into the partial download at `part_path`, resuming it when possible
+fn download_to_partial(url: &str, part_path: &Path, message: &str) -> anyhow::Result<()> {
+    let (response, mut part_file, resumed_from) = open_partial_download(url, part_path)?;
+    let content_length = response.content_length().or_else(|| {
+        response
+            .headers()
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    });
 
-    copy(&mut response, &mut tmp_file)?;
+    let progress_bar = create_download_progress_bar(
+        message.to_string(),
+        content_length.map(|content_length| resumed_from + content_length),
+    );
+    progress_bar.set_position(resumed_from);
+    let downloaded = resumed_from + copy(&mut progress_bar.wrap_read(response), &mut part_file)?;
     progress_bar.finish_and_clear();
 
-    Ok(filepath)
+    if let Some(content_length) = content_length {
+        if downloaded != resumed_from + content_length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "download was cut short: got {} of {} bytes",
+                    downloaded,
+                    resumed_from + content_length
+                ),
+            )
+            .into());
+        }
+    }
+    part_file.sync_all()?;
+    Ok(())
+}
+
+/// Suffix of the files downloads are written to until they are complete
+const PARTIAL_DOWNLOAD_SUFFIX: &str = ".part";
+
+/// Partial downloads that haven't been resumed for this long are removed
+const STALE_PARTIAL_DOWNLOAD_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+fn partial_download_path(filepath: &Path) -> PathBuf {
+    let mut filename = filepath.file_name().unwrap_or_default().to_os_string();
+    filename.push(PARTIAL_DOWNLOAD_SUFFIX);
+    filepath.with_file_name(filename)
 }
 
-pub fn download_and_install_wasmer(release: &Release, dest_dir: &PathBuf) -> anyhow::Result<()> {
-    let filepath = download_wasmer_to_cache(release)?;
+/// File next to a partial download holding the `ETag` or `Last-Modified` of the response it
+/// came from, which must still match for the download to be resumed
+fn partial_download_validator_path(part_path: &Path) -> PathBuf {
+    let mut filename = part_path.file_name().unwrap_or_default().to_os_string();
+    filename.push(".validator");
+    part_path.with_file_name(filename)
+}
 
-    let progress_bar = create_progress_bar(format!("Installing wasmer {}...", release.version()));
+/// Takes an exclusive lock on the download of `filepath`, waiting for any other wasmenv
+/// holding it. The lock file is left in place, as removing it would let a waiting wasmenv
+/// and a new one lock different files.
+fn lock_download(filepath: &Path) -> anyhow::Result<File> {
+    let mut filename = filepath.file_name().unwrap_or_default().to_os_string();
+    filename.push(".lock");
+    let lock_path = filepath.with_file_name(filename);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .context(format!("Open {:?}", lock_path))?;
+    file.lock().context(format!("Lock {:?}", lock_path))?;
+    Ok(file)
+}
+
+fn remove_partial_download(part_path: &Path) -> anyhow::Result<()> {
+    for path in [
+        part_path.to_path_buf(),
+        partial_download_validator_path(part_path),
+    ] {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Requests `url`, resuming the partial download at `part_path` with a `Range` request when
+/// there is one. The download starts over if the server ignores the range or the file changed
+/// since it was started.
+///
+/// Returns the response, the partial download to append the response body to, and the number
+/// of bytes that were already downloaded.
+fn open_partial_download(url: &str, part_path: &Path) -> anyhow::Result<(Response, File, u64)> {
+    let validator_path = partial_download_validator_path(part_path);
+    let downloaded = part_path.metadata().map(|meta| meta.len()).unwrap_or(0);
+    let validator = fs::read_to_string(&validator_path).ok();
+
+    let mut request = http_client()?.get(url);
+    let resuming = downloaded > 0 && validator.is_some();
+    if let (true, Some(validator)) = (resuming, &validator) {
+        request = request
+            .header(RANGE, format!("bytes={downloaded}-"))
+            .header(IF_RANGE, validator.trim());
+    }
+    let response = request.send()?;
+
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT
+            if resuming && content_range_start(&response) == Some(downloaded) =>
+        {
+            let file = OpenOptions::new().append(true).open(part_path)?;
+            return Ok((response, file, downloaded));
+        }
+        StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE if resuming => {
+            // what was downloaded so far doesn't line up with the file on the server
+            remove_partial_download(part_path)?;
+            return open_partial_download(url, part_path);
+        }
+        StatusCode::PARTIAL_CONTENT => {
+            return Err(anyhow::anyhow!(
+                "{} answered with a part of the file without being asked for one",
+                url
+            ));
+        }
+        _ => {}
+    }
+
+    let response = response.error_for_status()?;
+    let file = File::create(part_path)?;
+    // weak ETags can't be used with If-Range
+    let validator = [ETAG, LAST_MODIFIED]
+        .iter()
+        .filter_map(|name| response.headers().get(name)?.to_str().ok())
+        .find(|validator| !validator.starts_with("W/"));
+    match validator {
+        Some(validator) => fs::write(&validator_path, validator)?,
+        None if validator_path.exists() => fs::remove_file(&validator_path)?,
+        None => {}
+    }
+    Ok((response, file, 0))
+}
+
+/// Returns the first byte position of a `Content-Range: bytes <start>-<end>/<size>` header
+fn content_range_start(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Removes partial downloads which haven't been resumed in a long time
+fn remove_stale_partial_downloads(cache_dir: &Path) -> anyhow::Result<()> {
+    for entry in fs::read_dir(cache_dir)?.flatten() {
+        let filename = entry.file_name();
+        if !filename
+            .to_string_lossy()
+            .ends_with(PARTIAL_DOWNLOAD_SUFFIX)
+        {
+            continue;
+        }
+        let is_stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| modified.elapsed().unwrap_or_default() > STALE_PARTIAL_DOWNLOAD_AGE)
+            .unwrap_or(false);
+        if is_stale {
+            remove_partial_download(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn download_and_install(
+    runtime: &dyn Runtime,
+    release: &Release,
+    dest_dir: &PathBuf,
+) -> anyhow::Result<()> {
+    let filepath = download_to_cache(runtime, release)?;
+
+    let progress_bar = create_progress_bar(format!(
+        "Installing {} {}...",
+        runtime.name(),
+        release.version()
+    ));
 
     if !dest_dir.exists() {
         std::fs::create_dir_all(dest_dir).context(format!("Create {:?}", dest_dir))?;
     }
-    let file = File::open(filepath)?;
-    let decoder = GzDecoder::new(file);
-    let mut archive = Archive::new(decoder);
-
-    archive.unpack(dest_dir)?;
+    runtime.unpack(&filepath, dest_dir)?;
     progress_bar.finish_and_clear();
 
     Ok(())
 }
 
+/// Progress bars are only drawn when stderr is a terminal, so that logs and pipes stay clean
+fn progress_bar_hidden() -> bool {
+    !atty::is(atty::Stream::Stderr)
+}
+
 fn create_progress_bar(message: String) -> ProgressBar {
+    if progress_bar_hidden() {
+        return ProgressBar::hidden();
+    }
     let progress_bar = ProgressBar::new_spinner();
     progress_bar.set_style(ProgressStyle::default_spinner().tick_strings(&[
         "(●     )",
@@ -264,6 +823,28 @@ fn create_progress_bar(message: String) -> ProgressBar {
     progress_bar
 }
 
+/// Creates a progress bar showing the downloaded bytes, transfer rate and ETA, or a spinner if
+/// the size of the download is not known
+fn create_download_progress_bar(message: String, total_bytes: Option<u64>) -> ProgressBar {
+    let Some(total_bytes) = total_bytes else {
+        return create_progress_bar(format!("{message}..."));
+    };
+    if progress_bar_hidden() {
+        return ProgressBar::hidden();
+    }
+    let progress_bar = ProgressBar::new(total_bytes);
+    progress_bar.set_style(
+        ProgressStyle::with_template(
+            "{msg} [{bar:30}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, ETA {eta})",
+        )
+        .expect("Valid progress bar template")
+        .progress_chars("=> "),
+    );
+    progress_bar.set_message(message);
+
+    progress_bar
+}
+
 fn create_config_files(config_dir: &Path, wasmer_current_dir: &str) -> anyhow::Result<()> {
     let filepath = config_dir.join("wasmenv.sh");
     if !filepath.exists() {
@@ -335,27 +916,48 @@ pub fn verify_wasmenv_is_in_path() -> anyhow::Result<()> {
     }
 }
 
-pub fn release_to_install(
+/// Releases of the runtime matching `version` which can be installed, newest first
+pub fn releases_to_install(
+    runtime: &dyn Runtime,
     version: &Option<VersionReq>,
     install_prerelease: bool,
-) -> anyhow::Result<Option<Release>> {
-    // match exact by default
-    let mut releases = list_releases_interactively()?;
+) -> anyhow::Result<Vec<Release>> {
+    let mut releases = list_releases_interactively(runtime)?;
     if !install_prerelease {
         releases.retain(|rel| !rel.prerelease);
     }
-    let release = if let Some(req) = version {
-        releases.into_iter().find(|rel| req.matches(&rel.version()))
-    } else {
-        releases.first().cloned()
-    };
-    Ok(release)
+    if is_offline()? {
+        releases.retain(|rel| {
+            download_path(runtime, rel)
+                .map(|path| path.exists())
+                .unwrap_or(false)
+        });
+    }
+    if let Some(req) = version {
+        releases.retain(|rel| req.matches(&rel.version()));
+    }
+    Ok(releases)
+}
+
+pub fn release_to_install(
+    runtime: &dyn Runtime,
+    version: &Option<VersionReq>,
+    install_prerelease: bool,
+) -> anyhow::Result<Option<Release>> {
+    // match exact by default
+    Ok(releases_to_install(runtime, version, install_prerelease)?
+        .into_iter()
+        .next())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runtime::Wasmer;
     use std::env;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
 
     #[test]
     fn test_wasmenv_config_dir() -> anyhow::Result<()> {
@@ -367,7 +969,7 @@ mod tests {
 
     #[test]
     fn test_list_releases() -> anyhow::Result<()> {
-        let releases = list_releases()?;
+        let releases = fetch_releases(&Wasmer, None)?.unwrap().releases;
         assert!(!releases.is_empty());
         Ok(())
     }
@@ -390,19 +992,223 @@ mod tests {
     }
 
     #[test]
-    fn test_version_from_version_string() {
-        // Test the case where the version string is valid
-        let version_string = "wasmer 1.0.0".to_string();
-        let version = version_from_version_string(version_string).unwrap();
-        assert_eq!(version.to_string(), "1.0.0");
-
-        // Test the case where the version string is invalid
-        let version_string = "invalid version string".to_string();
-        let result = version_from_version_string(version_string);
-        assert!(result.is_err());
+    fn test_next_page_url() {
+        let link = "<https://api.github.com/repositories/1/releases?per_page=100&page=2>; rel=\"next\", \
+                    <https://api.github.com/repositories/1/releases?per_page=100&page=3>; rel=\"last\"";
         assert_eq!(
-            result.unwrap_err().to_string(),
-            "Could not get wasmer version form the version string"
+            next_page_url(link).as_deref(),
+            Some("https://api.github.com/repositories/1/releases?per_page=100&page=2")
+        );
+
+        let link = "<https://api.github.com/repositories/1/releases?per_page=100&page=2>; rel=\"prev\", \
+                    <https://api.github.com/repositories/1/releases?per_page=100&page=1>; rel=\"first\"";
+        assert_eq!(next_page_url(link), None);
+    }
+
+    #[test]
+    fn test_version_in_dir() {
+        let versions_dir = Path::new("/data/wasmenv");
+        assert_eq!(
+            version_in_dir(versions_dir, Path::new("/data/wasmenv/4.2.0/bin/wasmer")),
+            Some(Version::new(4, 2, 0))
+        );
+        assert_eq!(
+            version_in_dir(
+                versions_dir,
+                Path::new("/data/wasmenv/wasmtime/14.0.4/wasmtime")
+            ),
+            None
+        );
+        assert_eq!(
+            version_in_dir(versions_dir, Path::new("/home/user/.wasmer/bin/wasmer")),
+            None
+        );
+    }
+
+    /// Answers one request per connection on a local port with each of `responses` in turn
+    fn serve(listener: TcpListener, responses: Vec<String>) {
+        thread::spawn(move || {
+            for (stream, response) in listener.incoming().zip(responses) {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+    }
+
+    fn json_response(body: &str, headers: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+            Connection: close\r\n{}\r\n{}",
+            body.len(),
+            headers,
+            body
+        )
+    }
+
+    #[test]
+    fn test_fetch_releases_from_mirror() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let base_url = format!("http://{}", listener.local_addr()?);
+        let release = |tag: &str| {
+            format!(
+                r#"{{"tag_name": "{tag}", "prerelease": false, "published_at": "2023-05-01T00:00:00Z", "assets": []}}"#
+            )
+        };
+        serve(
+            listener,
+            vec![
+                json_response(
+                    &format!("[{}, {}]", release("v3.0.0"), release("dev")),
+                    &format!(
+                        "ETag: \"abc\"\r\nLink: <{base_url}/releases?page=2>; rel=\"next\"\r\n"
+                    ),
+                ),
+                json_response(&format!("[{}]", release("v4.0.0")), ""),
+            ],
+        );
+
+        let fetched = fetch_releases_from(&format!("{base_url}/releases"), None)?.unwrap();
+        let tags: Vec<_> = fetched.releases.iter().map(|rel| &rel.tag_name).collect();
+        assert_eq!(tags, ["v4.0.0", "v3.0.0"]);
+        assert_eq!(fetched.etag.as_deref(), Some("\"abc\""));
+        Ok(())
+    }
+
+    fn download_partially(
+        response: String,
+        part_contents: &str,
+    ) -> anyhow::Result<(String, u64, Option<String>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/wasmer.tar.gz", listener.local_addr()?);
+        serve(listener, vec![response]);
+
+        let dir = tempfile::tempdir()?;
+        let part_path = dir.path().join("wasmer.tar.gz.part");
+        fs::write(&part_path, part_contents)?;
+        fs::write(partial_download_validator_path(&part_path), "\"v1\"")?;
+
+        let (mut response, mut file, resumed_from) = open_partial_download(&url, &part_path)?;
+        copy(&mut response, &mut file)?;
+        drop(file);
+        Ok((
+            fs::read_to_string(&part_path)?,
+            resumed_from,
+            fs::read_to_string(partial_download_validator_path(&part_path)).ok(),
+        ))
+    }
+
+    #[test]
+    fn test_resume_partial_download() -> anyhow::Result<()> {
+        let response = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 6-10/11\r\n\
+            Content-Length: 5\r\nConnection: close\r\n\r\nworld";
+        let (contents, resumed_from, _) = download_partially(response.to_string(), "hello ")?;
+        assert_eq!(contents, "hello world");
+        assert_eq!(resumed_from, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restart_partial_download() -> anyhow::Result<()> {
+        let response = "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\n\
+            Content-Length: 11\r\nConnection: close\r\n\r\nhello there";
+        let (contents, resumed_from, validator) =
+            download_partially(response.to_string(), "hello ")?;
+        assert_eq!(contents, "hello there");
+        assert_eq!(resumed_from, 0);
+        assert_eq!(validator.as_deref(), Some("\"v2\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unrequested_partial_content() -> anyhow::Result<()> {
+        let response = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 6-10/11\r\n\
+            Content-Length: 5\r\nConnection: close\r\n\r\nworld";
+        assert!(download_partially(response.to_string(), "").is_err());
+        let response = "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Length: 0\r\n\
+            Connection: close\r\n\r\n";
+        assert!(download_partially(response.to_string(), "").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_server_error() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/releases", listener.local_addr()?);
+        serve(
+            listener,
+            vec![
+                "HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string(),
+                json_response("[]", ""),
+            ],
+        );
+
+        let fetched = fetch_releases_from(&url, None)?.unwrap();
+        assert!(fetched.releases.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_dropped_download() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/wasmer.tar.gz", listener.local_addr()?);
+        serve(
+            listener,
+            vec![
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 11\r\n\
+                Connection: close\r\n\r\nhello "
+                    .to_string(),
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 6-10/11\r\n\
+                Content-Length: 5\r\nConnection: close\r\n\r\nworld"
+                    .to_string(),
+            ],
+        );
+
+        let dir = tempfile::tempdir()?;
+        let part_path = dir.path().join("wasmer.tar.gz.part");
+        with_retries("Download", || download_to_partial(&url, &part_path, ""))?;
+        assert_eq!(fs::read_to_string(&part_path)?, "hello world");
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_download() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let filepath = dir.path().join("wasmer.tar.gz");
+        let lock = lock_download(&filepath)?;
+        let other = File::open(dir.path().join("wasmer.tar.gz.lock"))?;
+        assert!(other.try_lock().is_err());
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_github_api_url() {
+        assert!(is_github_api_url(
+            "https://api.github.com/repos/wasmerio/wasmer/releases"
+        ));
+        assert!(!is_github_api_url(
+            "https://api.github.com.example.net/repos/wasmerio/wasmer/releases"
+        ));
+        assert!(!is_github_api_url("https://api.github.com@example.net/"));
+        assert!(!is_github_api_url("http://api.github.com/repos"));
+    }
+
+    #[test]
+    fn test_pem_certificates() {
+        let bundle = "# corporate CA\n\
+            -----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n\
+            -----BEGIN CERTIFICATE-----\nMIIC\n-----END CERTIFICATE-----\n";
+        assert_eq!(
+            pem_certificates(bundle),
+            [
+                "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----",
+                "-----BEGIN CERTIFICATE-----\nMIIC\n-----END CERTIFICATE-----"
+            ]
         );
+        assert!(pem_certificates("not a certificate").is_empty());
     }
 }